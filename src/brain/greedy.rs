@@ -0,0 +1,42 @@
+use crate::brain::{Action, Brain, Observation};
+use crate::math::SimRng;
+
+/**
+Heads straight to the nearest food, keeps going when there is none.
+ */
+#[derive(Debug, Clone)]
+pub struct GreedyBrain {
+    pub speed: f32,
+}
+
+impl Default for GreedyBrain {
+    fn default() -> Self {
+        GreedyBrain { speed: 2.0 }
+    }
+}
+
+impl Brain for GreedyBrain {
+    fn decide(&self, observation: &Observation, _rng: &mut SimRng) -> Action {
+        let pos = observation.pos;
+        let nearest = observation.foods.iter()
+            .min_by(|a, b| pos.distance_to(a.pos).total_cmp(&pos.distance_to(b.pos)));
+
+        let heading = match nearest {
+            Some(food) => (food.pos.y - pos.y).atan2(food.pos.x - pos.x),
+            None => observation.heading.y.atan2(observation.heading.x),
+        };
+
+        Action {
+            heading,
+            speed: self.speed,
+            eat: true,
+        }
+    }
+
+    fn describe(&self) -> Vec<String> {
+        vec![
+            "Greedy: nearest food".to_string(),
+            format!("Speed: {:.2}", self.speed),
+        ]
+    }
+}
\ No newline at end of file
@@ -0,0 +1,79 @@
+pub mod weighted;
+pub mod neural;
+pub mod greedy;
+pub mod random_walk;
+pub mod scripted;
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use crate::brain::greedy::GreedyBrain;
+use crate::brain::random_walk::RandomWalkBrain;
+use crate::brain::scripted::ScriptedBrain;
+use crate::food::Food;
+use crate::math::{SimRng, Vec2};
+use crate::sim::rules::Rules;
+
+/**
+What a worm knows when it decides where to go.
+ */
+pub struct Observation<'a> {
+    pub tick: u64, // ticks since the world started
+    pub pos: Vec2,
+    pub heading: Vec2, // current direction
+    pub life: f32, // from 0 to 1
+    pub speed: f32,
+    pub foods: &'a [Food],
+    pub worms: &'a [Vec2], // every living worm, this one included
+    pub rules: &'a Rules,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Action {
+    pub heading: f32, // in radians
+    pub speed: f32,
+    #[serde(default = "always")]
+    pub eat: bool, // bite the food within reach
+}
+
+fn always() -> bool {
+    true
+}
+
+/**
+A steering strategy, the same brain drives the viewer and training.
+ */
+pub trait Brain: Debug {
+    fn decide(&self, observation: &Observation, rng: &mut SimRng) -> Action;
+
+    /**
+    Human readable parameters, shown when the worm is clicked.
+     */
+    fn describe(&self) -> Vec<String>;
+}
+
+/**
+Worms of a population can share one brain.
+ */
+pub type SharedBrain = Arc<dyn Brain + Send + Sync>;
+
+pub fn share<B: Brain + Clone + Send + Sync + 'static>(brain: &B) -> SharedBrain {
+    Arc::new(brain.clone())
+}
+
+/**
+Brains that need no training, by name: `greedy`, `random-walk` or `script:<file>`.
+ */
+pub fn builtin(name: &str) -> Result<SharedBrain, String> {
+    if let Some(path) = name.strip_prefix("script:") {
+        return ScriptedBrain::load(path)
+            .map(|brain| Arc::new(brain) as SharedBrain)
+            .map_err(|e| format!("{}: {}", path, e));
+    }
+
+    match name {
+        "greedy" => Ok(Arc::new(GreedyBrain::default())),
+        "random-walk" => Ok(Arc::new(RandomWalkBrain::default())),
+        _ => Err(format!("unknown strategy: {}", name)),
+    }
+}
\ No newline at end of file
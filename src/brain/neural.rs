@@ -0,0 +1,222 @@
+use burn::backend::NdArray;
+use burn::module::Param;
+use burn::nn::Linear;
+use burn::prelude::*;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+use crate::brain::{Action, Brain, Observation};
+use crate::math::SimRng;
+use genevo::genetic::Genotype;
+use crate::operators::{blend_gene, bound, BoundsKind, GeneSpec};
+use crate::train::Genome;
+
+type Cpu = NdArray;
+
+const NEAREST_FOODS: usize = 3;
+const INPUTS: usize = NEAREST_FOODS * 3 + 2; // (dx, dy, amount) per food, life, speed
+const HIDDEN: usize = 8;
+const OUTPUTS: usize = 3; // heading x, heading y, speed
+
+const MIN_SPEED: f32 = 0.5;
+const MAX_SPEED: f32 = 4.5;
+
+/**
+Every weight and bias, unbounded.
+ */
+const WEIGHT: GeneSpec = GeneSpec {
+    name: "weight",
+    min: f64::NEG_INFINITY,
+    max: f64::INFINITY,
+    initial: (-1.0, 1.0),
+    sigma: 0.3,
+};
+
+#[derive(Module, Debug)]
+pub struct Mlp<B: Backend> {
+    hidden: Linear<B>,
+    output: Linear<B>,
+}
+
+impl<B: Backend> Mlp<B> {
+    pub fn forward(&self, input: Tensor<B, 2>) -> Tensor<B, 2> {
+        let x = self.hidden.forward(input).tanh();
+        self.output.forward(x)
+    }
+}
+
+/**
+A small MLP brain. Only the weights are stored (and serialized),
+the burn module is rebuilt from them for every decision:
+the network is tiny and this keeps the brain cheap to clone and share between threads.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NeuralBrain {
+    pub(crate) hidden_weights: Vec<f32>, // INPUTS x HIDDEN
+    pub(crate) hidden_bias: Vec<f32>,
+    pub(crate) output_weights: Vec<f32>, // HIDDEN x OUTPUTS
+    pub(crate) output_bias: Vec<f32>,
+}
+
+impl NeuralBrain {
+    pub fn random<R: Rng>(rng: &mut R) -> NeuralBrain {
+        let (low, high) = WEIGHT.initial;
+        let mut weights = |n: usize| (0..n).map(|_| rng.gen_range(low as f32..high as f32)).collect::<Vec<f32>>();
+
+        NeuralBrain {
+            hidden_weights: weights(INPUTS * HIDDEN),
+            hidden_bias: weights(HIDDEN),
+            output_weights: weights(HIDDEN * OUTPUTS),
+            output_bias: weights(OUTPUTS),
+        }
+    }
+
+    /**
+    A hand-edited or truncated file would otherwise only fail on the first decision.
+     */
+    pub fn check_shape(&self) -> Result<(), String> {
+        let expected = [
+            ("hidden_weights", self.hidden_weights.len(), INPUTS * HIDDEN),
+            ("hidden_bias", self.hidden_bias.len(), HIDDEN),
+            ("output_weights", self.output_weights.len(), HIDDEN * OUTPUTS),
+            ("output_bias", self.output_bias.len(), OUTPUTS),
+        ];
+
+        for (name, len, expected_len) in expected {
+            if len != expected_len {
+                return Err(format!("{} has {} values, expected {}", name, len, expected_len));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn genes_mut(&mut self) -> impl Iterator<Item = &mut f32> {
+        self.hidden_weights.iter_mut()
+            .chain(self.hidden_bias.iter_mut())
+            .chain(self.output_weights.iter_mut())
+            .chain(self.output_bias.iter_mut())
+    }
+
+    pub(crate) fn genes(&self) -> impl Iterator<Item = &f32> {
+        self.hidden_weights.iter()
+            .chain(self.hidden_bias.iter())
+            .chain(self.output_weights.iter())
+            .chain(self.output_bias.iter())
+    }
+
+    fn model<B: Backend>(&self, device: &B::Device) -> Mlp<B> {
+        Mlp {
+            hidden: linear(&self.hidden_weights, &self.hidden_bias, INPUTS, HIDDEN, device),
+            output: linear(&self.output_weights, &self.output_bias, HIDDEN, OUTPUTS, device),
+        }
+    }
+}
+
+fn linear<B: Backend>(weights: &[f32], bias: &[f32], inputs: usize, outputs: usize, device: &B::Device) -> Linear<B> {
+    Linear {
+        weight: Param::from_tensor(Tensor::from_data(TensorData::new(weights.to_vec(), [inputs, outputs]), device)),
+        bias: Some(Param::from_tensor(Tensor::from_data(TensorData::new(bias.to_vec(), [outputs]), device))),
+    }
+}
+
+/**
+Nearest foods relative to the worm (scaled by the arena), then life and speed.
+ */
+fn inputs(observation: &Observation) -> Vec<f32> {
+    let rules = observation.rules;
+
+    let mut foods: Vec<_> = observation.foods.iter().collect();
+    foods.sort_by(|a, b| {
+        observation.pos.distance_to(a.pos).total_cmp(&observation.pos.distance_to(b.pos))
+    });
+
+    let mut inputs = Vec::with_capacity(INPUTS);
+    for i in 0..NEAREST_FOODS {
+        match foods.get(i) {
+            Some(food) => {
+                inputs.push((food.pos.x - observation.pos.x) / rules.width);
+                inputs.push((food.pos.y - observation.pos.y) / rules.height);
+                inputs.push(food.amount / rules.food_amount.1);
+            }
+            None => inputs.extend([0.0, 0.0, 0.0]),
+        }
+    }
+    inputs.push(observation.life);
+    inputs.push(observation.speed / MAX_SPEED);
+
+    inputs
+}
+
+impl Brain for NeuralBrain {
+    fn decide(&self, observation: &Observation, _rng: &mut SimRng) -> Action {
+        let device = Default::default();
+        let model = self.model::<Cpu>(&device);
+
+        let input = Tensor::<Cpu, 2>::from_data(TensorData::new(inputs(observation), [1, INPUTS]), &device);
+        let output = model.forward(input)
+            .into_data()
+            .to_vec::<f32>()
+            .expect("the network outputs f32");
+
+        let sigmoid = 1.0 / (1.0 + (-output[2]).exp());
+
+        Action {
+            heading: output[1].atan2(output[0]),
+            speed: MIN_SPEED + (MAX_SPEED - MIN_SPEED) * sigmoid,
+            eat: true,
+        }
+    }
+
+    fn describe(&self) -> Vec<String> {
+        let genes: Vec<f32> = self.genes().copied().collect();
+        let mean_abs = genes.iter().map(|w| w.abs()).sum::<f32>() / genes.len() as f32;
+
+        vec![
+            format!("Neural brain: {}-{}-{}", INPUTS, HIDDEN, OUTPUTS),
+            format!("Weights: {}", genes.len()),
+            format!("Mean |weight|: {:.3}", mean_abs),
+        ]
+    }
+}
+
+impl Genotype for NeuralBrain {
+    type Dna = f32;
+}
+
+impl Genome for NeuralBrain {
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        NeuralBrain::random(rng)
+    }
+
+    fn crossover<R: Rng>(&self, other: &Self, rng: &mut R) -> Self {
+        let mut child = self.clone();
+        for (gene, other_gene) in child.genes_mut().zip(other.genes()) {
+            if rng.gen_bool(0.5) {
+                *gene = *other_gene;
+            }
+        }
+        child
+    }
+
+    fn blend<R: Rng>(&self, other: &Self, alpha: f64, bounds: BoundsKind, rng: &mut R) -> Self {
+        let mut child = self.clone();
+        for (gene, other_gene) in child.genes_mut().zip(other.genes()) {
+            *gene = bound(blend_gene(*gene as f64, *other_gene as f64, alpha, rng), &WEIGHT, bounds) as f32;
+        }
+        child
+    }
+
+    fn gene_values(&self) -> Vec<f64> {
+        self.genes().map(|&gene| gene as f64).collect()
+    }
+
+    fn set_gene_values(&mut self, genes: &[f64]) {
+        for (gene, &value) in self.genes_mut().zip(genes) {
+            *gene = value as f32;
+        }
+    }
+
+    fn gene_specs(&self) -> Vec<GeneSpec> {
+        vec![WEIGHT; self.genes().count()]
+    }
+}
\ No newline at end of file
@@ -0,0 +1,41 @@
+use rand::Rng;
+use crate::brain::{Action, Brain, Observation};
+use crate::math::SimRng;
+
+/**
+Ignores the food, wanders by turning a random amount every tick.
+ */
+#[derive(Debug, Clone)]
+pub struct RandomWalkBrain {
+    pub speed: f32,
+    pub max_turn: f32, // in radians, per tick
+}
+
+impl Default for RandomWalkBrain {
+    fn default() -> Self {
+        RandomWalkBrain {
+            speed: 2.0,
+            max_turn: 0.5,
+        }
+    }
+}
+
+impl Brain for RandomWalkBrain {
+    fn decide(&self, observation: &Observation, rng: &mut SimRng) -> Action {
+        let current = observation.heading.y.atan2(observation.heading.x);
+
+        Action {
+            heading: current + rng.gen_range(-self.max_turn..=self.max_turn),
+            speed: self.speed,
+            eat: true,
+        }
+    }
+
+    fn describe(&self) -> Vec<String> {
+        vec![
+            "Random walk".to_string(),
+            format!("Speed: {:.2}", self.speed),
+            format!("Max turn: {:.2}", self.max_turn),
+        ]
+    }
+}
\ No newline at end of file
@@ -0,0 +1,49 @@
+use std::fs::File;
+use std::io;
+use serde::{Serialize, Deserialize};
+use crate::brain::{Action, Brain, Observation};
+use crate::math::SimRng;
+
+/**
+Replays a fixed list of actions, one per tick.
+Once the script is over the last action is repeated, or it starts again if `looped`.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScriptedBrain {
+    pub actions: Vec<Action>,
+    #[serde(default)]
+    pub looped: bool,
+}
+
+impl ScriptedBrain {
+    pub fn load(filename: &str) -> io::Result<ScriptedBrain> {
+        let file = File::open(filename)?;
+        let brain: ScriptedBrain = serde_json::from_reader(file)?;
+
+        if brain.actions.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the script has no actions"));
+        }
+
+        Ok(brain)
+    }
+}
+
+impl Brain for ScriptedBrain {
+    fn decide(&self, observation: &Observation, _rng: &mut SimRng) -> Action {
+        let tick = observation.tick as usize;
+        let i = if self.looped {
+            tick % self.actions.len()
+        } else {
+            tick.min(self.actions.len() - 1)
+        };
+
+        self.actions[i]
+    }
+
+    fn describe(&self) -> Vec<String> {
+        vec![
+            "Scripted".to_string(),
+            format!("Actions: {}{}", self.actions.len(), if self.looped { " (looped)" } else { "" }),
+        ]
+    }
+}
\ No newline at end of file
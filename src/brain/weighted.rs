@@ -0,0 +1,367 @@
+use std::f64::consts::PI;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+use crate::brain::{Action, Brain, Observation};
+use crate::food::Food;
+use crate::math::{SimRng, Vec2};
+use crate::operators::GeneSpec;
+use crate::train::ModelError;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WormBrain {
+    pub(crate) food_attraction: f64,
+    pub(crate) speed_factor: f64,
+    pub(crate) life_threshold: f64,
+    #[serde(default = "neutral")]
+    pub(crate) distance_falloff: f64, // a food pulls with its amount over its distance to this power
+    #[serde(default = "neutral")]
+    pub(crate) food_value_weight: f64, // power of the amount in the pull of a food
+    #[serde(default)]
+    pub(crate) crowd_avoidance: f64, // push away from the worms within CROWD_RADIUS
+    #[serde(default = "neutral")]
+    pub(crate) satiety: f64, // stops eating above this life, leaving the food to others
+    #[serde(default)]
+    pub(crate) turning_inertia: f64, // share of the turn towards the new heading held back every tick
+}
+
+/**
+Genes missing from older files steer the way those brains were trained to.
+ */
+fn neutral() -> f64 {
+    1.0
+}
+
+/**
+Every gene, in the order of `WormBrain::genes`.
+`crowd_avoidance` and `satiety` only matter to worms sharing the food, a lone worm doesn't select them.
+ */
+pub(crate) const GENES: [GeneSpec; 8] = [
+    GeneSpec { name: "food_attraction", min: 0.0, max: 1.0, initial: (0.5, 1.0), sigma: 0.1 },
+    GeneSpec { name: "speed_factor", min: 1.0, max: 3.0, initial: (1.0, 3.0), sigma: 0.2 },
+    GeneSpec { name: "life_threshold", min: 0.0, max: 1.0, initial: (0.2, 0.8), sigma: 0.1 },
+    GeneSpec { name: "distance_falloff", min: 0.0, max: 3.0, initial: (0.5, 2.0), sigma: 0.2 },
+    GeneSpec { name: "food_value_weight", min: 0.0, max: 2.0, initial: (0.5, 1.5), sigma: 0.1 },
+    GeneSpec { name: "crowd_avoidance", min: 0.0, max: 2.0, initial: (0.0, 1.0), sigma: 0.1 },
+    GeneSpec { name: "satiety", min: 0.0, max: 1.0, initial: (0.7, 1.0), sigma: 0.05 },
+    GeneSpec { name: "turning_inertia", min: 0.0, max: 0.95, initial: (0.0, 0.5), sigma: 0.05 },
+];
+
+/**
+Distance in pixels under which another worm pushes this one away.
+ */
+const CROWD_RADIUS: f64 = 100.0;
+
+impl WormBrain {
+    pub(crate) fn genes(&self) -> [f64; 8] {
+        [
+            self.food_attraction,
+            self.speed_factor,
+            self.life_threshold,
+            self.distance_falloff,
+            self.food_value_weight,
+            self.crowd_avoidance,
+            self.satiety,
+            self.turning_inertia,
+        ]
+    }
+
+    pub(crate) fn set_genes(&mut self, genes: &[f64]) {
+        self.food_attraction = genes[0];
+        self.speed_factor = genes[1];
+        self.life_threshold = genes[2];
+        self.distance_falloff = genes[3];
+        self.food_value_weight = genes[4];
+        self.crowd_avoidance = genes[5];
+        self.satiety = genes[6];
+        self.turning_inertia = genes[7];
+    }
+
+    /**
+    A brain with these genes, in the order of `genes`.
+     */
+    pub(crate) fn from_genes(genes: &[f64]) -> WormBrain {
+        let mut brain = WormBrain {
+            food_attraction: 0.0,
+            speed_factor: 0.0,
+            life_threshold: 0.0,
+            distance_falloff: 0.0,
+            food_value_weight: 0.0,
+            crowd_avoidance: 0.0,
+            satiety: 0.0,
+            turning_inertia: 0.0,
+        };
+        brain.set_genes(genes);
+        brain
+    }
+
+    /**
+    A hand-edited file could hold genes the training never produces.
+     */
+    pub fn check_genes(&self) -> Result<(), ModelError> {
+        for (spec, value) in GENES.iter().zip(self.genes()) {
+            if !(spec.min..=spec.max).contains(&value) {
+                return Err(ModelError::GeneOutOfRange { gene: spec.name, value, min: spec.min, max: spec.max });
+            }
+        }
+        Ok(())
+    }
+
+    /**
+    Heading (in radians, from -π to π) towards the foods: every food pulls with a unit vector
+    weighted by its amount to the `food_value_weight` over its distance to the `distance_falloff`,
+    and the pulls are summed as vectors.
+    Summing vectors instead of averaging angles keeps foods on both sides of the ±π seam
+    from cancelling out into the opposite direction.
+    With no food, or pulls that cancel out, the worm keeps its current `heading`.
+    The worms around then push it away, and `turning_inertia` holds back part of the turn.
+     */
+    pub(crate) fn calculate_direction<R: Rng>(
+        &self,
+        worm_pos: Vec2,
+        heading: Vec2,
+        foods: &[Food],
+        worms: &[Vec2],
+        rng: &mut R
+    ) -> f64 {
+        let current_angle = (heading.y as f64).atan2(heading.x as f64);
+        let food_angle = self.food_angle(worm_pos, current_angle, foods);
+        let (push_x, push_y) = self.crowd_push(worm_pos, worms);
+        let target_angle = (food_angle.sin() + push_y).atan2(food_angle.cos() + push_x);
+
+        // Small symmetric noise, none at all for a fully attracted worm
+        let random_offset = if foods.is_empty() {
+            0.0
+        } else {
+            rng.gen_range(-1.0..1.0) * 0.1 * (1.0 - self.food_attraction)
+        };
+
+        let turn = wrap_angle(target_angle + random_offset - current_angle);
+        wrap_angle(current_angle + turn * (1.0 - self.turning_inertia))
+    }
+
+    fn food_angle(&self, worm_pos: Vec2, current_angle: f64, foods: &[Food]) -> f64 {
+        if foods.is_empty() {
+            // When no food is available, move in a circular pattern
+            return wrap_angle(current_angle + 0.1);
+        }
+
+        let mut pull_x = 0.0;
+        let mut pull_y = 0.0;
+        let mut total_weight = 0.0;
+
+        for food in foods {
+            let dx = (food.pos.x - worm_pos.x) as f64;
+            let dy = (food.pos.y - worm_pos.y) as f64;
+            let distance = (dx * dx + dy * dy).sqrt();
+
+            if distance == 0.0 {
+                // already on it, it pulls nowhere
+                continue;
+            }
+
+            // Weight based on distance and food value
+            let weight = (food.amount as f64).powf(self.food_value_weight) / (distance + 1.0).powf(self.distance_falloff);
+
+            pull_x += dx / distance * weight;
+            pull_y += dy / distance * weight;
+            total_weight += weight;
+        }
+
+        let pull = (pull_x * pull_x + pull_y * pull_y).sqrt();
+        if pull <= total_weight * 1e-9 {
+            current_angle
+        } else {
+            pull_y.atan2(pull_x)
+        }
+    }
+
+    /**
+    Sum of the pushes of the worms within `CROWD_RADIUS`, away from each of them
+    and stronger the closer they are, up to `crowd_avoidance` for a worm right there.
+    The pull of the food weighs 1 against it.
+     */
+    fn crowd_push(&self, worm_pos: Vec2, worms: &[Vec2]) -> (f64, f64) {
+        if self.crowd_avoidance == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let mut push_x = 0.0;
+        let mut push_y = 0.0;
+        for other in worms {
+            let dx = (worm_pos.x - other.x) as f64;
+            let dy = (worm_pos.y - other.y) as f64;
+            let distance = (dx * dx + dy * dy).sqrt();
+
+            // itself, or a worm exactly on top that pushes nowhere
+            if distance == 0.0 || distance >= CROWD_RADIUS {
+                continue;
+            }
+
+            let strength = self.crowd_avoidance * (1.0 - distance / CROWD_RADIUS);
+            push_x += dx / distance * strength;
+            push_y += dy / distance * strength;
+        }
+        (push_x, push_y)
+    }
+}
+
+/**
+Same angle, from -π to π.
+ */
+fn wrap_angle(angle: f64) -> f64 {
+    let wrapped = angle.rem_euclid(2.0 * PI);
+    if wrapped > PI { wrapped - 2.0 * PI } else { wrapped }
+}
+
+impl Brain for WormBrain {
+    fn decide(&self, observation: &Observation, rng: &mut SimRng) -> Action {
+        let heading = self.calculate_direction(
+            observation.pos,
+            observation.heading,
+            observation.foods,
+            observation.worms,
+            rng,
+        );
+
+        // If the worm's life is below threshold, increase speed
+        let speed = if observation.life < self.life_threshold as f32 {
+            self.speed_factor * 1.5
+        } else {
+            self.speed_factor
+        };
+
+        Action {
+            heading: heading as f32,
+            speed: speed as f32,
+            eat: observation.life as f64 <= self.satiety,
+        }
+    }
+
+    fn describe(&self) -> Vec<String> {
+        vec![
+            format!("Food attraction: {:.3}", self.food_attraction),
+            format!("Speed factor: {:.3}", self.speed_factor),
+            format!("Life threshold: {:.3}", self.life_threshold),
+            format!("Distance falloff: {:.3}", self.distance_falloff),
+            format!("Food value weight: {:.3}", self.food_value_weight),
+            format!("Crowd avoidance: {:.3}", self.crowd_avoidance),
+            format!("Satiety: {:.3}", self.satiety),
+            format!("Turning inertia: {:.3}", self.turning_inertia),
+        ]
+    }
+}
+
+#[cfg(test)]
+fn focused_brain() -> WormBrain {
+    WormBrain {
+        food_attraction: 1.0, // no noise
+        speed_factor: 2.0,
+        life_threshold: 0.5,
+        distance_falloff: 1.0,
+        food_value_weight: 1.0,
+        crowd_avoidance: 0.0,
+        satiety: 1.0,
+        turning_inertia: 0.0,
+    }
+}
+
+#[cfg(test)]
+fn angle_between(a: f64, b: f64) -> f64 {
+    wrap_angle(a - b).abs()
+}
+
+#[test]
+fn symmetric_foods_pull_along_their_axis() {
+    let brain = focused_brain();
+    let mut rng = crate::math::seeded_rng(1);
+    let worm_pos = Vec2::new(400.0, 400.0);
+
+    for _ in 0..500 {
+        // two equal foods mirrored around a random axis, west included
+        let axis = rng.gen_range(-PI..PI);
+        let spread = rng.gen_range(0.0..PI / 2.0 - 0.01);
+        let distance = rng.gen_range(1.0..300.0);
+        let amount = rng.gen_range(1.0..100.0);
+
+        let foods: Vec<Food> = [axis - spread, axis + spread].iter()
+            .map(|angle| Food {
+                pos: Vec2::new(
+                    worm_pos.x + (distance * angle.cos()) as f32,
+                    worm_pos.y + (distance * angle.sin()) as f32,
+                ),
+                amount,
+            })
+            .collect();
+
+        let heading = Vec2::new(1.0, 0.0);
+        let direction = brain.calculate_direction(worm_pos, heading, &foods, &[], &mut rng);
+
+        assert!(
+            angle_between(direction, axis) < 1e-3,
+            "axis {:.3}, spread {:.3}: got {:.3}", axis, spread, direction
+        );
+    }
+}
+
+#[test]
+fn foods_across_the_seam_pull_west() {
+    let brain = focused_brain();
+    let mut rng = crate::math::seeded_rng(2);
+    let worm_pos = Vec2::new(400.0, 400.0);
+
+    // north-west and south-west, the old angle average pointed east
+    let foods = vec![
+        Food { pos: Vec2::new(300.0, 300.0), amount: 50.0 },
+        Food { pos: Vec2::new(300.0, 500.0), amount: 50.0 },
+    ];
+
+    let direction = brain.calculate_direction(worm_pos, Vec2::new(0.0, 1.0), &foods, &[], &mut rng);
+
+    assert!(angle_between(direction, PI) < 1e-6, "got {:.3}", direction);
+}
+
+#[test]
+fn cancelling_foods_keep_the_heading() {
+    let brain = focused_brain();
+    let mut rng = crate::math::seeded_rng(3);
+    let worm_pos = Vec2::new(400.0, 400.0);
+    let heading = Vec2::new(0.0, -1.0);
+
+    let foods = vec![
+        Food { pos: Vec2::new(300.0, 400.0), amount: 50.0 },
+        Food { pos: Vec2::new(500.0, 400.0), amount: 50.0 },
+    ];
+
+    let direction = brain.calculate_direction(worm_pos, heading, &foods, &[], &mut rng);
+
+    assert!(angle_between(direction, -PI / 2.0) < 1e-6, "got {:.3}", direction);
+}
+
+#[test]
+fn crowded_and_sated_worms_leave_the_food() {
+    use crate::sim::rules::Rules;
+
+    let brain = WormBrain { crowd_avoidance: 2.0, satiety: 0.8, ..focused_brain() };
+    let mut rng = crate::math::seeded_rng(4);
+    let worm_pos = Vec2::new(400.0, 400.0);
+    let foods = vec![Food { pos: Vec2::new(500.0, 400.0), amount: 50.0 }];
+    // a worm just east of this one, between it and the food
+    let worms = vec![worm_pos, Vec2::new(410.0, 400.0)];
+    let rules = Rules::default();
+
+    let observe = |life| Observation {
+        tick: 0,
+        pos: worm_pos,
+        heading: Vec2::new(1.0, 0.0),
+        life,
+        speed: 1.0,
+        foods: &foods,
+        worms: &worms,
+        rules: &rules,
+    };
+
+    let action = brain.decide(&observe(0.9), &mut rng);
+    assert!(angle_between(action.heading as f64, PI) < 1e-6, "got {:.3}", action.heading);
+    assert!(!action.eat);
+    assert!(brain.decide(&observe(0.5), &mut rng).eat);
+}
\ No newline at end of file
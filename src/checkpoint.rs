@@ -0,0 +1,69 @@
+use std::fs::File;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use crate::cma::CmaEs;
+use crate::hall_of_fame::HallOfFame;
+use crate::math::SimRng;
+use crate::train::TrainConfig;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/**
+Everything needed to pick a training run up where it stopped: the last evaluated generation,
+the best brains so far, the settings and the state of the RNG when that generation started.
+Resuming replays that generation, so the run goes on exactly as if it had never stopped.
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Checkpoint<G> {
+    pub generation: usize, // from 0
+    pub population: Vec<G>,
+    pub hall_of_fame: HallOfFame<G>,
+    #[serde(default)]
+    pub cma: Option<CmaEs>, // the search distribution, with CMA-ES
+    #[serde(default)]
+    pub step_size: Option<f64>, // of a self-adaptive mutation, with the genetic algorithm
+    pub config: TrainConfig,
+    pub rng: SimRng,
+}
+
+/**
+Just the generation and the settings, to know which brain a checkpoint holds before loading it.
+ */
+#[derive(Deserialize)]
+pub struct CheckpointHeader {
+    pub generation: usize,
+    pub config: TrainConfig,
+}
+
+pub fn load_checkpoint<G: DeserializeOwned>(filename: &str) -> io::Result<Checkpoint<G>> {
+    let file = File::open(filename)?;
+    Ok(serde_json::from_reader(file)?)
+}
+
+pub fn checkpoint_header(filename: &str) -> io::Result<CheckpointHeader> {
+    let file = File::open(filename)?;
+    Ok(serde_json::from_reader(file)?)
+}
+
+/**
+From now on Ctrl-C only asks the training loop to stop,
+it writes a last checkpoint once the current generation is done.
+A second Ctrl-C quits at once, without a checkpoint.
+ */
+pub fn catch_interrupt() {
+    let handler = || {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        eprintln!("Stopping after this generation, Ctrl-C again to quit now");
+    };
+    if let Err(e) = ctrlc::set_handler(handler) {
+        eprintln!("Failed to catch Ctrl-C, an interrupted run won't be checkpointed: {}", e);
+    }
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
\ No newline at end of file
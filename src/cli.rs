@@ -0,0 +1,355 @@
+use std::str::FromStr;
+use crate::fitness::{Fitness, Term};
+use crate::train::{BrainKind, TrainConfig};
+
+/**
+What the binary takes, for a mistyped subcommand.
+ */
+pub const USAGE: &str = "usage: [train | map-elites | sweep | evaluate | benchmark] [--flag value ...], \
+or only the flags of the viewer (--seed, --model, --strategy, --hall-of-fame, --evolve, --evolve-output)";
+
+/**
+Flags of `train` followed by a value, also taken by the subcommands built on its settings.
+ */
+const TRAIN_FLAGS: &[&str] = &[
+    "--optimiser", "--population", "--generations", "--elite", "--mutation-rate", "--selection",
+    "--selection-ratio", "--tournament-size", "--crossover", "--blend-alpha", "--mutation",
+    "--mutation-scale", "--bounds", "--reinsertion", "--cma-sigma", "--seed", "--width", "--height",
+    "--food", "--eat-radius", "--food-radius-scale", "--food-min", "--food-max", "--bite",
+    "--energy-per-food", "--drain", "--step-scale", "--iterations", "--threads", "--episodes",
+    "--validation", "--holdout", "--hall-of-fame", "--seed-from", "--fitness", "--group-size",
+    "--survival-weight", "--extinction-weight", "--equality-weight", "--objective", "--brain",
+    "--output", "--save-population", "--checkpoint", "--checkpoint-every", "--metrics",
+];
+
+const RESUME_FLAGS: &[&str] = &[
+    "--resume", "--generations", "--threads", "--output", "--save-population", "--checkpoint",
+    "--checkpoint-every", "--metrics",
+];
+
+/**
+Rejects any argument that is neither one of the `flags` with its value nor one of the `switches`,
+so a misspelled flag isn't silently ignored.
+ */
+pub fn check_flags(args: &[String], flags: &[&[&str]], switches: &[&str]) -> Result<(), String> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if flags.iter().any(|flags| flags.contains(&arg.as_str())) {
+            args.next();
+        } else if !switches.contains(&arg.as_str()) {
+            return Err(if arg.starts_with("--") {
+                format!("unknown flag: {}", arg)
+            } else {
+                format!("unexpected argument: {}", arg)
+            });
+        }
+    }
+    Ok(())
+}
+
+/**
+The viewer takes `--seed`, `--model`, `--strategy` and `--evolve-output`,
+and the switches `--evolve` and `--hall-of-fame`.
+ */
+pub fn check_viewer_args(args: &[String]) -> Result<(), String> {
+    check_flags(args, &[&["--seed", "--model", "--strategy", "--evolve-output"]], &["--evolve", "--hall-of-fame"])
+}
+
+/**
+Looks up `--name <value>` in the arguments and parses the value.
+Returns `Ok(None)` when the flag is missing.
+ */
+pub fn flag_value<T: FromStr>(args: &[String], name: &str) -> Result<Option<T>, String> {
+    let Some(i) = args.iter().position(|arg| arg == name) else {
+        return Ok(None);
+    };
+
+    let raw = args.get(i + 1)
+        .ok_or_else(|| format!("missing value for {}", name))?;
+
+    raw.parse::<T>()
+        .map(Some)
+        .map_err(|_| format!("invalid value for {}: {}", name, raw))
+}
+
+/**
+Every value of a flag that can be repeated, like `--model a.json --model b.json`.
+ */
+pub fn flag_values(args: &[String], name: &str) -> Vec<String> {
+    args.windows(2)
+        .filter(|pair| pair[0] == name)
+        .map(|pair| pair[1].clone())
+        .collect()
+}
+
+/**
+Same as `flag_value`, but a malformed value ends the process.
+ */
+pub fn flag_or_exit<T: FromStr>(args: &[String], name: &str) -> Option<T> {
+    flag_value(args, name).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    })
+}
+
+/**
+Builds the training config from the `train` subcommand flags,
+anything not given keeps its default.
+ */
+pub fn parse_train_args(args: &[String]) -> Result<TrainConfig, String> {
+    check_flags(args, &[TRAIN_FLAGS], &["--independent-layouts"])?;
+    train_config(args)
+}
+
+fn train_config(args: &[String]) -> Result<TrainConfig, String> {
+    let mut config = TrainConfig::default();
+
+    if let Some(v) = flag_value(args, "--optimiser")? { config.optimiser = v; }
+    if let Some(v) = flag_value(args, "--population")? { config.population_size = v; }
+    if let Some(v) = flag_value(args, "--generations")? { config.generations = v; }
+    if let Some(v) = flag_value(args, "--elite")? { config.elite_fraction = v; }
+    if let Some(v) = flag_value(args, "--mutation-rate")? { config.mutation_rate = v; }
+    if let Some(v) = flag_value(args, "--selection")? { config.selection = v; }
+    if let Some(v) = flag_value(args, "--selection-ratio")? { config.selection_ratio = v; }
+    if let Some(v) = flag_value(args, "--tournament-size")? { config.tournament_size = v; }
+    if let Some(v) = flag_value(args, "--crossover")? { config.crossover = v; }
+    if let Some(v) = flag_value(args, "--blend-alpha")? { config.blend_alpha = v; }
+    if let Some(v) = flag_value(args, "--mutation")? { config.mutation = v; }
+    if let Some(v) = flag_value(args, "--mutation-scale")? { config.mutation_scale = v; }
+    if let Some(v) = flag_value(args, "--bounds")? { config.bounds = v; }
+    if let Some(v) = flag_value(args, "--reinsertion")? { config.reinsertion = v; }
+    if let Some(v) = flag_value(args, "--cma-sigma")? { config.cma_sigma = v; }
+    if let Some(v) = flag_value(args, "--seed")? { config.seed = v; }
+    if let Some(v) = flag_value(args, "--width")? { config.rules.width = v; }
+    if let Some(v) = flag_value(args, "--height")? { config.rules.height = v; }
+    if let Some(v) = flag_value(args, "--food")? { config.num_food = v; }
+    if let Some(v) = flag_value(args, "--eat-radius")? { config.rules.eat_radius = v; }
+    if let Some(v) = flag_value(args, "--food-radius-scale")? { config.rules.food_radius_scale = v; }
+    if let Some(v) = flag_value(args, "--food-min")? { config.rules.food_amount.0 = v; }
+    if let Some(v) = flag_value(args, "--food-max")? { config.rules.food_amount.1 = v; }
+    if let Some(v) = flag_value(args, "--bite")? { config.rules.bite = v; }
+    if let Some(v) = flag_value(args, "--energy-per-food")? { config.rules.energy_per_food = v; }
+    if let Some(v) = flag_value(args, "--drain")? { config.rules.drain = v; }
+    if let Some(v) = flag_value(args, "--step-scale")? { config.rules.step_scale = v; }
+    if let Some(v) = flag_value(args, "--iterations")? { config.iterations = v; }
+    if let Some(v) = flag_value(args, "--threads")? { config.threads = Some(v); }
+    if let Some(v) = flag_value(args, "--episodes")? { config.episodes = v; }
+    if args.iter().any(|arg| arg == "--independent-layouts") { config.common_layouts = false; }
+    if let Some(v) = flag_value(args, "--validation")? { config.validation_episodes = v; }
+    if let Some(v) = flag_value(args, "--holdout")? { config.holdout_episodes = v; }
+    if let Some(v) = flag_value(args, "--hall-of-fame")? { config.hall_of_fame = v; }
+    if let Some(v) = flag_value(args, "--seed-from")? { config.seed_from = Some(v); }
+    if let Some(v) = flag_value(args, "--fitness")? { config.fitness = v; }
+    if let Some(v) = flag_value(args, "--group-size")? { config.group_size = v; }
+    if let Some(v) = flag_value(args, "--survival-weight")? { config.survival_weight = v; }
+    if let Some(v) = flag_value(args, "--extinction-weight")? { config.extinction_weight = v; }
+    if let Some(v) = flag_value(args, "--equality-weight")? { config.equality_weight = v; }
+    let objectives = flag_values(args, "--objective");
+    if !objectives.is_empty() {
+        config.objectives = objectives.iter().map(|term| term.parse()).collect::<Result<Vec<Term>, String>>()?;
+    } else {
+        // spelled out, so the model metadata says what the fitness was
+        config.objectives = Fitness::from_config(&config).terms;
+    }
+    if let Some(v) = flag_value(args, "--brain")? { config.brain = v; }
+    if let Some(v) = flag_value(args, "--output")? { config.output = v; }
+    else if config.brain == BrainKind::Neural { config.output = "neural_model.json".to_string(); }
+    if let Some(v) = flag_value(args, "--save-population")? { config.population_output = Some(v); }
+    if let Some(v) = flag_value(args, "--checkpoint")? { config.checkpoint = Some(v); }
+    if let Some(v) = flag_value(args, "--checkpoint-every")? { config.checkpoint_every = v; }
+    if let Some(v) = flag_value(args, "--metrics")? { config.metrics = Some(v); }
+
+    validate(&config)?;
+
+    Ok(config)
+}
+
+/**
+Rejects settings no run can go with, whatever they came from.
+ */
+pub fn validate(config: &TrainConfig) -> Result<(), String> {
+    if config.population_size < 2 {
+        return Err("--population must be at least 2".to_string());
+    }
+    if config.generations == 0 {
+        return Err("--generations must be at least 1".to_string());
+    }
+    if config.episodes == 0 || config.validation_episodes == 0 || config.holdout_episodes == 0 {
+        return Err("--episodes, --validation and --holdout must be at least 1".to_string());
+    }
+    if config.threads == Some(0) {
+        return Err("--threads must be at least 1".to_string());
+    }
+    if config.hall_of_fame == 0 {
+        return Err("--hall-of-fame must be at least 1".to_string());
+    }
+    if config.group_size == 0 {
+        return Err("--group-size must be at least 1".to_string());
+    }
+    if config.objectives.iter().any(|term| !term.weight.is_finite()) {
+        return Err("--objective weights must be finite".to_string());
+    }
+    if !(config.elite_fraction > 0.0 && config.elite_fraction <= 1.0) {
+        return Err("--elite must be in (0, 1]".to_string());
+    }
+    if !(0.0..=1.0).contains(&config.mutation_rate) {
+        return Err("--mutation-rate must be in [0, 1]".to_string());
+    }
+    if !(config.selection_ratio > 0.0 && config.selection_ratio <= 1.0) {
+        return Err("--selection-ratio must be in (0, 1]".to_string());
+    }
+    if config.tournament_size == 0 || config.tournament_size > config.population_size {
+        return Err("--tournament-size must be between 1 and the population size".to_string());
+    }
+    if config.mutation_scale <= 0.0 {
+        return Err("--mutation-scale must be positive".to_string());
+    }
+    if config.cma_sigma <= 0.0 {
+        return Err("--cma-sigma must be positive".to_string());
+    }
+    if config.blend_alpha < 0.0 {
+        return Err("--blend-alpha must not be negative".to_string());
+    }
+    if config.rules.width <= 0.0 || config.rules.height <= 0.0 {
+        return Err("--width and --height must be positive".to_string());
+    }
+    let rules = &config.rules;
+    if !(rules.food_amount.0 > 0.0 && rules.food_amount.0 < rules.food_amount.1) {
+        return Err("--food-min must be positive and below --food-max".to_string());
+    }
+    if rules.bite <= 0.0 || rules.step_scale <= 0.0 || rules.food_radius_scale <= 0.0 {
+        return Err("--bite, --step-scale and --food-radius-scale must be positive".to_string());
+    }
+    if rules.eat_radius < 0.0 || rules.energy_per_food < 0.0 || rules.drain < 0.0 {
+        return Err("--eat-radius, --energy-per-food and --drain must not be negative".to_string());
+    }
+
+    Ok(())
+}
+
+/**
+`train --resume <checkpoint>` keeps the settings saved in the checkpoint,
+only the length of the run and where its results go can change.
+The run goes on from `generation` (from 0), so it needs more generations than that.
+ */
+pub fn parse_resume_args(args: &[String], mut config: TrainConfig, generation: usize) -> Result<TrainConfig, String> {
+    check_flags(args, &[RESUME_FLAGS], &[])?;
+    if let Some(v) = flag_value(args, "--generations")? { config.generations = v; }
+    if let Some(v) = flag_value(args, "--threads")? { config.threads = Some(v); }
+    if let Some(v) = flag_value(args, "--output")? { config.output = v; }
+    if let Some(v) = flag_value(args, "--save-population")? { config.population_output = Some(v); }
+    if let Some(v) = flag_value(args, "--checkpoint")? { config.checkpoint = Some(v); }
+    if let Some(v) = flag_value(args, "--checkpoint-every")? { config.checkpoint_every = v; }
+    if let Some(v) = flag_value(args, "--metrics")? { config.metrics = Some(v); }
+
+    if config.generations <= generation {
+        return Err(format!(
+            "the checkpoint goes on from generation {}, --generations must be at least that, got {}",
+            generation + 1, config.generations
+        ));
+    }
+    if config.threads == Some(0) {
+        return Err("--threads must be at least 1".to_string());
+    }
+
+    Ok(config)
+}
+
+pub struct MapElitesArgs {
+    pub config: TrainConfig,
+    pub bins: usize, // per behaviour descriptor
+    pub archive: String,
+    pub heatmap: Option<String>,
+}
+
+/**
+`map-elites` takes the flags of `train`, plus `--bins`, `--archive <file>` and `--heatmap <image>`.
+ */
+pub fn parse_map_elites_args(args: &[String]) -> Result<MapElitesArgs, String> {
+    check_flags(args, &[TRAIN_FLAGS, &["--bins", "--archive", "--heatmap"]], &["--independent-layouts"])?;
+    let config = train_config(args)?;
+    let bins = flag_value(args, "--bins")?.unwrap_or(10);
+    let archive = flag_value(args, "--archive")?.unwrap_or_else(|| "map_archive.json".to_string());
+    let heatmap = flag_value(args, "--heatmap")?;
+
+    if bins == 0 {
+        return Err("--bins must be at least 1".to_string());
+    }
+
+    Ok(MapElitesArgs { config, bins, archive, heatmap })
+}
+
+pub struct SweepArgs {
+    pub spec: String,
+    pub summary: Option<String>, // CSV of the ranked configurations
+}
+
+/**
+`sweep --spec <file>`, optionally with `--summary <csv>`.
+ */
+pub fn parse_sweep_args(args: &[String]) -> Result<SweepArgs, String> {
+    check_flags(args, &[&["--spec", "--summary"]], &[])?;
+    let spec = flag_value(args, "--spec")?.ok_or_else(|| "sweep needs a --spec file".to_string())?;
+    let summary = flag_value(args, "--summary")?;
+
+    Ok(SweepArgs { spec, summary })
+}
+
+pub struct EvaluateArgs {
+    pub config: TrainConfig, // arena, seed and episode length
+    pub episodes: usize,
+    pub models: Vec<String>,
+    pub strategies: Vec<String>,
+}
+
+/**
+`evaluate` takes the arena flags of `train`, plus one or two brains to play
+the same seeded episodes: `--model <file>` or `--strategy <name>`.
+ */
+pub fn parse_evaluate_args(args: &[String]) -> Result<EvaluateArgs, String> {
+    check_flags(args, &[TRAIN_FLAGS, &["--model", "--strategy"]], &[])?;
+    let config = train_config(args)?;
+    let episodes = flag_value(args, "--episodes")?.unwrap_or(100);
+    let models = flag_values(args, "--model");
+    let strategies = flag_values(args, "--strategy");
+
+    if !(1..=2).contains(&(models.len() + strategies.len())) {
+        return Err("evaluate takes one or two brains, with --model or --strategy".to_string());
+    }
+    if episodes < 2 {
+        return Err("--episodes must be at least 2".to_string());
+    }
+
+    Ok(EvaluateArgs { config, episodes, models, strategies })
+}
+
+pub struct BenchmarkArgs {
+    pub config: TrainConfig, // arena, seed and episode length
+    pub episodes: usize,
+    pub models: Vec<String>,
+    pub strategies: Vec<String>,
+}
+
+/**
+`benchmark` takes the arena flags of `train`, plus any number of
+`--model <file>` and `--strategy <name>` to compare.
+ */
+pub fn parse_benchmark_args(args: &[String]) -> Result<BenchmarkArgs, String> {
+    check_flags(args, &[TRAIN_FLAGS, &["--model", "--strategy"]], &[])?;
+    let config = train_config(args)?;
+    let episodes = flag_value(args, "--episodes")?.unwrap_or(20);
+    let mut models = flag_values(args, "--model");
+    let mut strategies = flag_values(args, "--strategy");
+
+    if models.is_empty() && strategies.is_empty() {
+        models.push("worm_model.json".to_string());
+        strategies.push("greedy".to_string());
+        strategies.push("random-walk".to_string());
+    }
+
+    if episodes == 0 {
+        return Err("--episodes must be at least 1".to_string());
+    }
+
+    Ok(BenchmarkArgs { config, episodes, models, strategies })
+}
\ No newline at end of file
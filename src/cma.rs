@@ -0,0 +1,206 @@
+use rand::Rng;
+use rand_distr::StandardNormal;
+use serde::{Serialize, Deserialize};
+
+/**
+Covariance matrix adaptation evolution strategy, maximising.
+Samples are drawn around `mean` from a normal distribution whose shape (`cov`)
+and size (`sigma`) follow the steps that paid off in the previous generations.
+Everything is recomputed from these fields, so a checkpoint only needs them.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CmaEs {
+    mean: Vec<f64>,
+    sigma: f64,
+    cov: Vec<Vec<f64>>,
+    path_sigma: Vec<f64>, // evolution path of the step size
+    path_cov: Vec<f64>, // evolution path of the covariance
+    generation: usize, // updates so far
+}
+
+impl CmaEs {
+    pub fn new(mean: Vec<f64>, sigma: f64) -> CmaEs {
+        let n = mean.len();
+        CmaEs {
+            cov: (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect(),
+            path_sigma: vec![0.0; n],
+            path_cov: vec![0.0; n],
+            mean,
+            sigma,
+            generation: 0,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn mean(&self) -> &[f64] {
+        &self.mean
+    }
+
+    /**
+    `count` new points to evaluate.
+     */
+    pub fn ask<R: Rng>(&self, count: usize, rng: &mut R) -> Vec<Vec<f64>> {
+        let n = self.mean.len();
+        let (values, vectors) = eigen(&self.cov);
+        let scales: Vec<f64> = values.iter().map(|&value| value.max(0.0).sqrt()).collect();
+
+        (0..count)
+            .map(|_| {
+                let z: Vec<f64> = (0..n).map(|_| rng.sample(StandardNormal)).collect();
+                (0..n)
+                    .map(|i| {
+                        let y: f64 = (0..n).map(|k| vectors[i][k] * scales[k] * z[k]).sum();
+                        self.mean[i] + self.sigma * y
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /**
+    Moves the distribution towards the best half of `samples`, `fitness` in the same order.
+     */
+    pub fn tell(&mut self, samples: &[Vec<f64>], fitness: &[f64]) {
+        let n = self.mean.len() as f64;
+        let dim = self.mean.len();
+        let lambda = samples.len();
+        let mu = (lambda / 2).max(1);
+
+        let raw: Vec<f64> = (1..=mu).map(|i| (mu as f64 + 0.5).ln() - (i as f64).ln()).collect();
+        let total: f64 = raw.iter().sum();
+        let weights: Vec<f64> = raw.iter().map(|weight| weight / total).collect();
+        let mu_eff = 1.0 / weights.iter().map(|weight| weight * weight).sum::<f64>();
+
+        let c_sigma = (mu_eff + 2.0) / (n + mu_eff + 5.0);
+        let d_sigma = 1.0 + 2.0 * (((mu_eff - 1.0) / (n + 1.0)).sqrt() - 1.0).max(0.0) + c_sigma;
+        let c_c = (4.0 + mu_eff / n) / (n + 4.0 + 2.0 * mu_eff / n);
+        let c_1 = 2.0 / ((n + 1.3).powi(2) + mu_eff);
+        let c_mu = (1.0 - c_1).min(2.0 * (mu_eff - 2.0 + 1.0 / mu_eff) / ((n + 2.0).powi(2) + mu_eff));
+        let chi_n = n.sqrt() * (1.0 - 1.0 / (4.0 * n) + 1.0 / (21.0 * n * n));
+
+        let mut ranked: Vec<usize> = (0..lambda).collect();
+        ranked.sort_by(|&a, &b| fitness[b].total_cmp(&fitness[a]));
+
+        // steps of the best samples, before scaling by sigma
+        let steps: Vec<Vec<f64>> = ranked.iter()
+            .take(mu)
+            .map(|&i| (0..dim).map(|j| (samples[i][j] - self.mean[j]) / self.sigma).collect())
+            .collect();
+        let step: Vec<f64> = (0..dim)
+            .map(|j| steps.iter().zip(&weights).map(|(y, weight)| weight * y[j]).sum())
+            .collect();
+
+        for (mean, step) in self.mean.iter_mut().zip(&step) {
+            *mean += self.sigma * step;
+        }
+
+        // C^-1/2 * step, with C = B D² Bᵀ
+        let (values, vectors) = eigen(&self.cov);
+        let rotated: Vec<f64> = (0..dim)
+            .map(|k| (0..dim).map(|i| vectors[i][k] * step[i]).sum::<f64>() / values[k].max(1e-20).sqrt())
+            .collect();
+        let whitened: Vec<f64> = (0..dim)
+            .map(|i| (0..dim).map(|k| vectors[i][k] * rotated[k]).sum())
+            .collect();
+
+        let sigma_rate = (c_sigma * (2.0 - c_sigma) * mu_eff).sqrt();
+        for (path, whitened) in self.path_sigma.iter_mut().zip(&whitened) {
+            *path = (1.0 - c_sigma) * *path + sigma_rate * whitened;
+        }
+        let path_sigma_norm = self.path_sigma.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+        self.generation += 1;
+        let stalled = path_sigma_norm / (1.0 - (1.0 - c_sigma).powi(2 * self.generation as i32)).sqrt()
+            >= (1.4 + 2.0 / (n + 1.0)) * chi_n;
+        let h_sigma = if stalled { 0.0 } else { 1.0 };
+
+        let cov_rate = (c_c * (2.0 - c_c) * mu_eff).sqrt();
+        for (path, step) in self.path_cov.iter_mut().zip(&step) {
+            *path = (1.0 - c_c) * *path + h_sigma * cov_rate * step;
+        }
+
+        let decay = 1.0 - c_1 - c_mu + (1.0 - h_sigma) * c_1 * c_c * (2.0 - c_c);
+        for i in 0..dim {
+            for j in 0..dim {
+                let rank_mu: f64 = steps.iter().zip(&weights).map(|(y, weight)| weight * y[i] * y[j]).sum();
+                self.cov[i][j] = decay * self.cov[i][j]
+                    + c_1 * self.path_cov[i] * self.path_cov[j]
+                    + c_mu * rank_mu;
+            }
+        }
+
+        self.sigma *= ((c_sigma / d_sigma) * (path_sigma_norm / chi_n - 1.0)).exp();
+    }
+}
+
+/**
+Eigenvalues and eigenvectors (as the columns) of a symmetric matrix, by cyclic Jacobi rotations.
+ */
+fn eigen(matrix: &[Vec<f64>]) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let n = matrix.len();
+    let mut a = matrix.to_vec();
+    let mut v: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+
+    for _ in 0..100 {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[i][j] * a[i][j])
+            .sum();
+        if off_diagonal < 1e-30 {
+            break;
+        }
+
+        for p in 0..n {
+            for q in p + 1..n {
+                if a[p][q].abs() < 1e-300 {
+                    continue;
+                }
+
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+
+                for row in a.iter_mut() {
+                    let (kp, kq) = (row[p], row[q]);
+                    row[p] = c * kp - s * kq;
+                    row[q] = s * kp + c * kq;
+                }
+                for k in 0..n {
+                    let (pk, qk) = (a[p][k], a[q][k]);
+                    a[p][k] = c * pk - s * qk;
+                    a[q][k] = s * pk + c * qk;
+                }
+                for row in v.iter_mut() {
+                    let (kp, kq) = (row[p], row[q]);
+                    row[p] = c * kp - s * kq;
+                    row[q] = s * kp + c * kq;
+                }
+            }
+        }
+    }
+
+    ((0..n).map(|i| a[i][i]).collect(), v)
+}
+
+#[test]
+fn climbs_a_tilted_ridge() {
+    // a narrow diagonal ridge peaking at (0.3, -0.2, 0.5)
+    let peak = [0.3, -0.2, 0.5];
+    let height = |x: &[f64]| {
+        let d: Vec<f64> = x.iter().zip(&peak).map(|(x, peak)| x - peak).collect();
+        -((d[0] + d[1]).powi(2) + 100.0 * (d[0] - d[1]).powi(2) + 10.0 * d[2] * d[2])
+    };
+
+    let mut rng = crate::math::seeded_rng(5);
+    let mut cma = CmaEs::new(vec![0.0; 3], 0.5);
+    for _ in 0..150 {
+        let samples = cma.ask(12, &mut rng);
+        let fitness: Vec<f64> = samples.iter().map(|x| height(x)).collect();
+        cma.tell(&samples, &fitness);
+    }
+
+    for (x, peak) in cma.mean().iter().zip(&peak) {
+        assert!((x - peak).abs() < 1e-4, "{:?}", cma.mean());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,148 @@
+use std::cmp::Ordering;
+use crate::brain::SharedBrain;
+use crate::fitness::{episode_rollouts, Episode, Fitness, Rollout};
+use crate::train::{with_threads, TrainConfig};
+
+/**
+Mean of a sample, with the margin of its 95% confidence interval (from Student's t).
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Estimate {
+    pub mean: f64,
+    pub margin: f64, // the interval is the mean plus or minus the margin
+}
+
+/**
+Two-sided 95% quantiles of Student's t, for 1 to 30 degrees of freedom.
+ */
+const T_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+];
+
+fn t_95(degrees_of_freedom: usize) -> f64 {
+    match T_95.get(degrees_of_freedom.wrapping_sub(1)) {
+        Some(&t) => t,
+        // within 0.002 of the exact quantile past 30
+        None => 1.96 + 2.5 / degrees_of_freedom as f64,
+    }
+}
+
+impl Estimate {
+    pub fn of(values: &[f64]) -> Estimate {
+        if values.is_empty() {
+            return Estimate::default();
+        }
+
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        if values.len() < 2 {
+            return Estimate { mean, margin: 0.0 };
+        }
+
+        let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        Estimate { mean, margin: t_95(values.len() - 1) * (variance / n).sqrt() }
+    }
+}
+
+/**
+How a brain did over the episodes of an evaluation.
+ */
+#[derive(Debug, Clone)]
+pub struct Evaluation {
+    pub scores: Vec<f64>, // fitness of every episode
+    pub fitness: Estimate,
+    pub lifetime: Estimate, // ticks a worm lived
+    pub food_eaten: Estimate, // by a worm
+}
+
+pub fn evaluate(brain: &SharedBrain, config: &TrainConfig, episodes: &[Episode]) -> Evaluation {
+    let rollouts = with_threads(config.threads, || episode_rollouts(brain, 0.0, config, episodes));
+    let of = |value: &dyn Fn(&Rollout) -> f64| rollouts.iter().map(value).collect::<Vec<f64>>();
+
+    let fitness = Fitness::from_config(config);
+    let scores = of(&|rollout| fitness.of(rollout));
+    Evaluation {
+        fitness: Estimate::of(&scores),
+        lifetime: Estimate::of(&of(&|rollout| rollout.lifetime)),
+        food_eaten: Estimate::of(&of(&|rollout| rollout.food_eaten)),
+        scores,
+    }
+}
+
+/**
+Mann–Whitney U test, two-sided: does one sample tend to score higher than the other?
+Makes no assumption on the distribution of the scores, which pile up at 0 and at all food eaten.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MannWhitney {
+    pub u: f64, // pairs where the first sample is higher, ties counting half
+    pub superiority: f64, // chance a score of the first sample beats one of the second
+    pub p_value: f64, // normal approximation, corrected for ties and continuity
+}
+
+pub fn mann_whitney(a: &[f64], b: &[f64]) -> MannWhitney {
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let n = n1 + n2;
+
+    let mut values: Vec<(f64, bool)> = a.iter().map(|&value| (value, true))
+        .chain(b.iter().map(|&value| (value, false)))
+        .collect();
+    values.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap_or(Ordering::Equal));
+
+    // tied values share the mean of their ranks
+    let mut rank_sum = 0.0;
+    let mut ties = 0.0;
+    let mut start = 0;
+    while start < values.len() {
+        let end = start + values[start..].iter().take_while(|(value, _)| *value == values[start].0).count();
+        let rank = (start + end + 1) as f64 / 2.0;
+        rank_sum += rank * values[start..end].iter().filter(|(_, first)| *first).count() as f64;
+        let tied = (end - start) as f64;
+        ties += tied.powi(3) - tied;
+        start = end;
+    }
+
+    let u = rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let pairs = n1 * n2;
+    let variance = pairs / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    let p_value = if variance > 0.0 {
+        let z = ((u - pairs / 2.0).abs() - 0.5).max(0.0) / variance.sqrt();
+        erfc(z / std::f64::consts::SQRT_2)
+    } else {
+        1.0
+    };
+
+    MannWhitney { u, superiority: u / pairs.max(1.0), p_value: p_value.min(1.0) }
+}
+
+/**
+Complementary error function for x ≥ 0, within 1.5e-7 (Abramowitz and Stegun 7.1.26).
+ */
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let polynomial = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    polynomial * (-x * x).exp()
+}
+
+#[test]
+fn mann_whitney_matches_reference_values() {
+    // same as scipy.stats.mannwhitneyu(a, b, method="asymptotic")
+    let a = [1.0, 2.0, 3.0, 4.0, 5.0];
+    let b = [6.0, 7.0, 8.0, 9.0, 10.0];
+    let test = mann_whitney(&a, &b);
+    assert_eq!(test.u, 0.0);
+    assert_eq!(test.superiority, 0.0);
+    assert!((test.p_value - 0.01219).abs() < 1e-4, "{}", test.p_value);
+
+    let tied = mann_whitney(&[0.0, 0.0, 3.0, 5.0, 6.0, 6.0], &[0.0, 3.0, 4.0, 1.0]);
+    assert_eq!(tied.u, 15.5);
+    assert!((tied.p_value - 0.51469).abs() < 1e-4, "{}", tied.p_value);
+
+    assert_eq!(mann_whitney(&[2.0, 2.0], &[2.0, 2.0]).p_value, 1.0);
+
+    let estimate = Estimate::of(&[1.0, 2.0, 3.0]);
+    assert_eq!(estimate.mean, 2.0);
+    assert!((estimate.margin - 2.484).abs() < 1e-3);
+}
\ No newline at end of file
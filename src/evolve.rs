@@ -0,0 +1,141 @@
+use rand::Rng;
+use crate::brain::{share, SharedBrain};
+use crate::brain::weighted::WormBrain;
+use crate::math::{seeded_rng, SimRng};
+use crate::operators::Mutator;
+use crate::sim::rules::Rules;
+use crate::sim::World;
+use crate::train::{random_brain, save_model, Genome, Metadata, TrainConfig, WormModel};
+
+/**
+Runs a genetic algorithm inside the viewer: once every worm has starved,
+the `elite_fraction` that ate the most is kept and their children fill the rest.
+ */
+pub struct Evolution {
+    pub generation: usize,
+    pub best_fitness: f32, // of the last finished generation
+    pub mean_fitness: f32,
+    pub best: Option<WormBrain>,
+    pub output: String, // best genome of each generation is saved here
+    population: Vec<WormBrain>, // brain of the worm with the same id
+    num_food: i32,
+    config: TrainConfig,
+    rng: SimRng,
+}
+
+impl Evolution {
+    /**
+    Starts from the `initial` brains, or from random ones if there are none.
+     */
+    pub fn new(num_worms: i32, rules: Rules, num_food: i32, initial: Vec<WormBrain>, output: &str, seed: u64) -> Evolution {
+        let mut rng = seeded_rng(seed);
+        let population = (0..num_worms as usize)
+            .map(|id| match initial.get(id % initial.len().max(1)) {
+                Some(brain) => brain.clone(),
+                None => random_brain(&mut rng),
+            })
+            .collect();
+
+        Evolution {
+            generation: 1,
+            best_fitness: 0.0,
+            mean_fitness: 0.0,
+            best: None,
+            output: output.to_string(),
+            population,
+            num_food,
+            config: TrainConfig {
+                population_size: num_worms as usize,
+                rules,
+                num_food,
+                seed,
+                ..TrainConfig::default()
+            },
+            rng,
+        }
+    }
+
+    pub fn assign_brains(&self, world: &mut World) {
+        let brains: Vec<SharedBrain> = self.population.iter()
+            .map(share)
+            .collect();
+        world.assign_brains(&brains);
+    }
+
+    /**
+    Scores the run that just ended and returns a fresh world with the bred generation.
+     */
+    pub fn next_generation(&mut self, world: &World) -> World {
+        let mut ranked: Vec<(&WormBrain, f32)> = world.worms.iter()
+            .chain(world.dead.iter())
+            .filter_map(|worm| self.population.get(worm.id).map(|brain| (brain, worm.food_eaten)))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mut next = World::random(
+            world.rules.clone(),
+            self.population.len() as i32,
+            self.num_food,
+            self.rng.gen()
+        );
+
+        if !ranked.is_empty() {
+            self.best_fitness = ranked[0].1;
+            self.mean_fitness = ranked.iter().map(|&(_, fitness)| fitness).sum::<f32>() / ranked.len() as f32;
+
+            let best = ranked[0].0.clone();
+            let elites: Vec<&WormBrain> = ranked.iter()
+                .take(elite_count(ranked.len(), self.config.elite_fraction))
+                .map(|&(brain, _)| brain)
+                .collect();
+
+            let population = breed(&elites, self.population.len(), &Mutator::new(&self.config), &mut self.rng);
+            self.population = population;
+            self.best = Some(best);
+            self.generation += 1;
+        }
+
+        self.assign_brains(&mut next);
+        next
+    }
+
+    pub fn save_best(&self) -> std::io::Result<()> {
+        let Some(brain) = &self.best else {
+            return Ok(());
+        };
+
+        let metadata = Metadata {
+            generation: self.generation - 1, // `best` bred the current one
+            fitness: "food eaten by one worm among the whole viewer population".to_string(),
+            config: self.config.clone(),
+        };
+        let model = WormModel::new(brain.clone(), self.best_fitness as f64, Some(metadata));
+        save_model(&model, &self.output)
+    }
+}
+
+/**
+How many of the best brains are kept into the next generation, at least one.
+ */
+fn elite_count(population_size: usize, elite_fraction: f64) -> usize {
+    ((population_size as f64 * elite_fraction).ceil() as usize).max(1)
+}
+
+/**
+The next generation: the `elites` kept as they are,
+the rest are mutated children of random pairs of them.
+ */
+fn breed<G: Genome, R: Rng>(elites: &[&G], population_size: usize, mutator: &Mutator, rng: &mut R) -> Vec<G> {
+    let mut new_population: Vec<G> = elites.iter().map(|&brain| brain.clone()).collect();
+
+    while new_population.len() < population_size {
+        let parent1 = elites[rng.gen_range(0..elites.len())];
+        let parent2 = elites[rng.gen_range(0..elites.len())];
+
+        let mut child = parent1.crossover(parent2, rng);
+        child.mutate(mutator, rng);
+        new_population.push(child);
+    }
+
+    new_population
+}
\ No newline at end of file
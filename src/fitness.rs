@@ -0,0 +1,633 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use genevo::genetic::FitnessFunction;
+use rand::Rng;
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::brain::{share, SharedBrain};
+use crate::food::Food;
+use crate::food::generate::generate_food;
+use crate::math::{seeded_rng, SimRng};
+use crate::sim::rules::Rules;
+use crate::sim::World;
+use crate::train::{evaluate_brain, Genome, TrainConfig};
+use crate::worm::Worm;
+use crate::worm::generate::generate_worms;
+
+/**
+Who plays an episode, which also picks the objectives when the config names none.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FitnessMode {
+    Solo, // a single worm, rewarded for the food it eats
+    Cooperative, // a whole group of worms with this brain, rewarded for sharing the food well
+}
+
+impl FromStr for FitnessMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "solo" => Ok(FitnessMode::Solo),
+            "cooperative" => Ok(FitnessMode::Cooperative),
+            _ => Err(format!("unknown fitness: {}", s)),
+        }
+    }
+}
+
+/**
+One measure of how an episode went, averaged over the worms that played it.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Objective {
+    Food, // food eaten
+    Lifetime, // ticks survived
+    Efficiency, // food eaten per pixel travelled
+    Fairness, // one minus the Gini coefficient of the food eaten, 1 for a lone worm
+    Survivors, // share of the worms alive at the end
+    Extinction, // share of the episode before the last worm starved, 1 if some survived
+}
+
+impl FromStr for Objective {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "food" => Ok(Objective::Food),
+            "lifetime" => Ok(Objective::Lifetime),
+            "efficiency" => Ok(Objective::Efficiency),
+            "fairness" => Ok(Objective::Fairness),
+            "survivors" => Ok(Objective::Survivors),
+            "extinction" => Ok(Objective::Extinction),
+            _ => Err(format!("unknown objective: {}", s)),
+        }
+    }
+}
+
+impl Objective {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Objective::Food => "food",
+            Objective::Lifetime => "lifetime",
+            Objective::Efficiency => "efficiency",
+            Objective::Fairness => "fairness",
+            Objective::Survivors => "survivors",
+            Objective::Extinction => "extinction",
+        }
+    }
+
+    pub fn of(&self, rollout: &Rollout) -> f64 {
+        match self {
+            Objective::Food => rollout.food_eaten,
+            Objective::Lifetime => rollout.lifetime,
+            // a worm standing still on its food would otherwise be infinitely efficient
+            Objective::Efficiency => rollout.food_eaten / rollout.distance.max(1.0),
+            Objective::Fairness => rollout.fairness,
+            Objective::Survivors => rollout.survivors,
+            Objective::Extinction => rollout.extinction,
+        }
+    }
+
+    /**
+    The most an episode played with `config` can score, the least being 0.
+     */
+    fn highest(&self, config: &TrainConfig) -> f64 {
+        match self {
+            // every food at its largest amount
+            Objective::Food | Objective::Efficiency => (config.rules.food_amount.1 * config.num_food as f32) as f64,
+            Objective::Lifetime => config.iterations as f64,
+            Objective::Fairness | Objective::Survivors | Objective::Extinction => 1.0,
+        }
+    }
+}
+
+/**
+An objective and its weight in the fitness, `name` or `name:weight` on the command line.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Term {
+    pub objective: Objective,
+    pub weight: f64,
+}
+
+impl FromStr for Term {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, weight) = s.split_once(':').unwrap_or((s, "1"));
+        let weight = weight.parse().map_err(|_| format!("invalid weight of {}: {}", name, weight))?;
+        Ok(Term { objective: name.parse()?, weight })
+    }
+}
+
+/**
+What a brain is rewarded for in an episode: a weighted sum of objectives.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fitness {
+    pub terms: Vec<Term>,
+}
+
+impl Fitness {
+    /**
+    The objectives of `config`, or by default the food eaten by a lone worm,
+    and the survivors, extinction and fairness of a cooperative group with their weights.
+     */
+    pub fn from_config(config: &TrainConfig) -> Fitness {
+        if !config.objectives.is_empty() {
+            return Fitness { terms: config.objectives.clone() };
+        }
+
+        let term = |objective, weight| Term { objective, weight };
+        let terms = match config.fitness {
+            FitnessMode::Solo => vec![term(Objective::Food, 1.0)],
+            FitnessMode::Cooperative => vec![
+                term(Objective::Survivors, config.survival_weight),
+                term(Objective::Extinction, config.extinction_weight),
+                term(Objective::Fairness, config.equality_weight),
+            ],
+        };
+        Fitness { terms }
+    }
+
+    pub fn of(&self, rollout: &Rollout) -> f64 {
+        self.terms.iter()
+            .map(|term| term.weight * term.objective.of(rollout))
+            .sum()
+    }
+
+    pub fn describe(&self) -> String {
+        self.terms.iter()
+            .map(|term| format!("{} x{}", term.objective.name(), term.weight))
+            .collect::<Vec<String>>()
+            .join(" + ")
+    }
+
+    /**
+    Bounds of the fitness of an episode, a negative weight swapping those of its objective.
+     */
+    fn range(&self, config: &TrainConfig) -> (f64, f64) {
+        self.terms.iter().fold((0.0, 0.0), |(lowest, highest), term| {
+            let extreme = term.weight * term.objective.highest(config);
+            (lowest + extreme.min(0.0), highest + extreme.max(0.0))
+        })
+    }
+}
+
+/**
+One rollout to play: a food layout and the seed of the world.
+ */
+#[derive(Debug, Clone)]
+pub struct Episode {
+    pub foods: Vec<Food>,
+    pub seed: u64,
+}
+
+pub fn sample_episodes(n: usize, rules: &Rules, num_food: i32, rng: &mut SimRng) -> Vec<Episode> {
+    (0..n)
+        .map(|_| Episode {
+            foods: generate_food(num_food, rules, rng),
+            seed: rng.gen(),
+        })
+        .collect()
+}
+
+/**
+Fitness of `brain` in each of the `episodes`, as the objectives of `config` define it.
+Episodes are played in parallel, each world seeded from its own episode
+so the scores don't depend on the number of threads.
+ */
+pub fn episode_scores(brain: &SharedBrain, config: &TrainConfig, episodes: &[Episode]) -> Vec<f64> {
+    let fitness = Fitness::from_config(config);
+    episode_rollouts(brain, 0.0, config, episodes).iter()
+        .map(|rollout| fitness.of(rollout))
+        .collect()
+}
+
+/**
+How one episode went for a brain, on average over the worms that played it.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rollout {
+    pub food_eaten: f64,
+    pub lifetime: f64, // ticks lived, at most the length of the episode
+    pub distance: f64, // travelled, in pixels
+    pub fairness: f64, // one minus the Gini coefficient of the food eaten
+    pub survivors: f64, // share of the worms alive at the end
+    pub extinction: f64, // share of the episode before the last worm starved, 1 if some survived
+    pub behaviour: Behaviour,
+}
+
+/**
+Same as `episode_scores`, along with how the worms lived and behaved,
+hungry while their life is below `hunger_threshold`.
+ */
+pub fn episode_rollouts(brain: &SharedBrain, hunger_threshold: f64, config: &TrainConfig, episodes: &[Episode]) -> Vec<Rollout> {
+    episodes.par_iter()
+        .map(|episode| match config.fitness {
+            FitnessMode::Solo => evaluate_brain(
+                brain,
+                &config.rules,
+                &episode.foods,
+                episode.seed,
+                config.iterations,
+                hunger_threshold
+            ),
+            FitnessMode::Cooperative => evaluate_group(brain, config, episode, hunger_threshold),
+        })
+        .collect()
+}
+
+/**
+How worms behaved over an episode, averaged over every tick each of them was alive.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Behaviour {
+    pub speed: f64,
+    pub hunger: f64, // share of the time spent below the hunger threshold
+}
+
+impl Behaviour {
+    /**
+    Mean of the behaviours of several episodes.
+     */
+    pub fn mean(behaviours: &[Behaviour]) -> Behaviour {
+        let n = behaviours.len().max(1) as f64;
+        Behaviour {
+            speed: behaviours.iter().map(|behaviour| behaviour.speed).sum::<f64>() / n,
+            hunger: behaviours.iter().map(|behaviour| behaviour.hunger).sum::<f64>() / n,
+        }
+    }
+}
+
+/**
+Adds up the behaviour of the living worms, tick after tick.
+ */
+#[derive(Debug, Default)]
+pub struct BehaviourTally {
+    speed: f64,
+    hungry: usize,
+    samples: usize, // worms times ticks
+}
+
+impl BehaviourTally {
+    pub fn observe(&mut self, worms: &[Worm], hunger_threshold: f64) {
+        for worm in worms {
+            self.speed += worm.speed as f64;
+            if (worm.life as f64) < hunger_threshold {
+                self.hungry += 1;
+            }
+            self.samples += 1;
+        }
+    }
+
+    pub fn behaviour(&self) -> Behaviour {
+        let samples = self.samples.max(1) as f64;
+        Behaviour {
+            speed: self.speed / samples,
+            hunger: self.hungry as f64 / samples,
+        }
+    }
+
+    /**
+    Ticks each of the `worms` lived, on average.
+     */
+    pub fn lifetime(&self, worms: usize) -> f64 {
+        self.samples as f64 / worms.max(1) as f64
+    }
+}
+
+/**
+Steps `world` for `iterations` ticks, or until every worm starved, and sums up how it went.
+ */
+pub fn play(mut world: World, iterations: usize, hunger_threshold: f64) -> Rollout {
+    let worms = world.worms.len() + world.dead.len();
+    let mut tally = BehaviourTally::default();
+    let mut extinction_tick = None; // the tick the last worm starved in
+    for tick in 0..iterations {
+        world.step(1.0);
+        tally.observe(&world.worms, hunger_threshold);
+
+        if world.worms.is_empty() {
+            extinction_tick = Some(tick);
+            break;
+        }
+    }
+
+    let everyone: Vec<&Worm> = world.worms.iter().chain(world.dead.iter()).collect();
+    let eaten: Vec<f64> = everyone.iter().map(|worm| worm.food_eaten as f64).collect();
+    let per_worm = |total: f64| total / worms.max(1) as f64;
+
+    Rollout {
+        food_eaten: per_worm(eaten.iter().sum()),
+        lifetime: tally.lifetime(worms),
+        distance: per_worm(everyone.iter().map(|worm| worm.distance as f64).sum()),
+        fairness: 1.0 - gini(&eaten),
+        survivors: per_worm(world.worms.len() as f64),
+        extinction: extinction_tick.map_or(1.0, |tick| tick as f64 / iterations.max(1) as f64),
+        behaviour: tally.behaviour(),
+    }
+}
+
+/**
+What the fitness of a model trained with `config` measures, for its metadata.
+ */
+pub fn describe_fitness(config: &TrainConfig) -> String {
+    format!(
+        "{}, mean over the {} training episodes of {} ticks of its generation",
+        describe_score(config),
+        config.episodes,
+        config.iterations
+    )
+}
+
+/**
+What the score of one episode measures.
+ */
+pub fn describe_score(config: &TrainConfig) -> String {
+    let fitness = Fitness::from_config(config).describe();
+    match config.fitness {
+        FitnessMode::Solo => format!("{} of a lone worm", fitness),
+        FitnessMode::Cooperative => format!("{} of {} worms sharing the food", fitness, config.group_size),
+    }
+}
+
+/**
+`config.group_size` worms, all steered by `brain`, scattered over the episode's food.
+A worm that eats more than it needs leaves less for the others, which fairness and survivors penalise.
+ */
+pub fn evaluate_group(brain: &SharedBrain, config: &TrainConfig, episode: &Episode, hunger_threshold: f64) -> Rollout {
+    let mut rng = seeded_rng(episode.seed);
+    let mut worms = generate_worms(config.group_size as i32, &config.rules, &mut rng);
+    for worm in worms.iter_mut() {
+        worm.brain = Some(brain.clone());
+    }
+
+    play(World::new(config.rules.clone(), worms, episode.foods.clone(), rng), config.iterations, hunger_threshold)
+}
+
+/**
+Gini coefficient: mean absolute difference between all pairs, over twice the mean.
+ */
+pub fn gini(values: &[f64]) -> f64 {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    if values.is_empty() || mean <= 0.0 {
+        return 0.0;
+    }
+
+    let differences: f64 = values.iter()
+        .flat_map(|a| values.iter().map(move |b| (a - b).abs()))
+        .sum();
+    differences / (2.0 * n * n * mean)
+}
+
+/**
+Mean and (sample) variance of the scores of a brain over several episodes.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Score {
+    pub mean: f64,
+    pub variance: f64,
+}
+
+impl Score {
+    pub fn of(scores: &[f64]) -> Score {
+        if scores.is_empty() {
+            return Score::default();
+        }
+
+        let n = scores.len() as f64;
+        let mean = scores.iter().sum::<f64>() / n;
+        let variance = if scores.len() > 1 {
+            scores.iter().map(|score| (score - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+
+        Score { mean, variance }
+    }
+}
+
+/**
+The episodes of the current generation. With common layouts every brain plays the same ones,
+otherwise each brain draws its own from `seed` and its genes.
+ */
+#[derive(Debug)]
+struct Layouts {
+    seed: u64,
+    common: Option<Vec<Episode>>,
+}
+
+/**
+Fitness of a brain: its mean score over `episodes` freshly sampled episodes.
+The layouts change every generation (see `next_generation`) so a brain can't overfit one of them.
+It counts the episodes it plays, as genevo also scores the children it reinserts
+and scores again the brains it keeps.
+ */
+#[derive(Debug, Clone)]
+pub struct RolloutFitness {
+    config: TrainConfig,
+    layouts: Arc<RwLock<Layouts>>, // shared with the clones genevo keeps
+    episodes_played: Arc<AtomicUsize>, // since the last `take_episodes_played`
+}
+
+impl RolloutFitness {
+    pub fn new(config: &TrainConfig) -> RolloutFitness {
+        RolloutFitness {
+            config: config.clone(),
+            layouts: Arc::new(RwLock::new(Layouts { seed: 0, common: None })),
+            episodes_played: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn sample(&self, rng: &mut SimRng) -> Vec<Episode> {
+        sample_episodes(self.config.episodes, &self.config.rules, self.config.num_food, rng)
+    }
+
+    /**
+    Samples the layouts of the next generation, to call before each step of the simulation.
+     */
+    pub fn next_generation(&self, rng: &mut SimRng) {
+        let seed = rng.gen();
+        let common = if self.config.common_layouts {
+            Some(self.sample(rng))
+        } else {
+            None
+        };
+
+        *self.layouts.write().unwrap() = Layouts { seed, common };
+    }
+
+    /**
+    The episodes `brain` plays in the current generation.
+     */
+    pub fn episodes_for<G: Genome>(&self, brain: &G) -> Vec<Episode> {
+        let layouts = self.layouts.read().unwrap();
+        match &layouts.common {
+            Some(episodes) => episodes.clone(),
+            None => {
+                let mut rng = seeded_rng(layouts.seed ^ genes_hash(brain));
+                self.sample(&mut rng)
+            }
+        }
+    }
+
+    /**
+    Whether every brain plays the same episodes in the current generation.
+     */
+    pub fn shares_episodes(&self) -> bool {
+        self.layouts.read().unwrap().common.is_some()
+    }
+
+    pub fn score<G: Genome>(&self, brain: &G) -> Score {
+        self.score_on(brain, &self.episodes_for(brain))
+    }
+
+    /**
+    Score of `brain` over `episodes`, counted like the ones of the generation.
+     */
+    pub fn score_on<G: Genome>(&self, brain: &G, episodes: &[Episode]) -> Score {
+        self.episodes_played.fetch_add(episodes.len(), AtomicOrdering::Relaxed);
+        Score::of(&episode_scores(&share(brain), &self.config, episodes))
+    }
+
+    /**
+    Episodes played through `score` since the last call, to compare the cost of optimisers.
+     */
+    pub fn take_episodes_played(&self) -> usize {
+        self.episodes_played.swap(0, AtomicOrdering::Relaxed)
+    }
+}
+
+/**
+Same genes, same hash: the episodes of a brain don't depend on the evaluation order,
+nor on what else the genome carries. FNV-1a over the bits of the genes,
+which unlike the hasher of std stays the same across Rust releases.
+ */
+fn genes_hash<G: Genome>(brain: &G) -> u64 {
+    brain.gene_values().iter()
+        .flat_map(|gene| gene.to_bits().to_le_bytes())
+        .fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+impl<G: Genome> FitnessFunction<G, FitnessValue> for RolloutFitness {
+    fn fitness_of(&self, brain: &G) -> FitnessValue {
+        FitnessValue(self.score(brain).mean)
+    }
+
+    fn average(&self, values: &[FitnessValue]) -> FitnessValue {
+        FitnessValue(values.iter().map(|value| value.0).sum::<f64>() / values.len().max(1) as f64)
+    }
+
+    fn highest_possible_fitness(&self) -> FitnessValue {
+        FitnessValue(Fitness::from_config(&self.config).range(&self.config).1)
+    }
+
+    fn lowest_possible_fitness(&self) -> FitnessValue {
+        FitnessValue(Fitness::from_config(&self.config).range(&self.config).0)
+    }
+}
+
+/**
+A fitness genevo can rank: it needs a total order, which `f64` lacks.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct FitnessValue(pub f64);
+
+impl PartialEq for FitnessValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FitnessValue {}
+
+impl PartialOrd for FitnessValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FitnessValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl genevo::genetic::Fitness for FitnessValue {
+    fn zero() -> Self {
+        FitnessValue(0.0)
+    }
+
+    fn abs_diff(&self, other: &Self) -> Self {
+        FitnessValue((self.0 - other.0).abs())
+    }
+}
+
+#[test]
+fn score_of_scores() {
+    let score = Score::of(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+
+    assert_eq!(score.mean, 5.0);
+    assert!((score.variance - 32.0 / 7.0).abs() < 1e-12);
+    assert_eq!(Score::of(&[3.0]).variance, 0.0);
+}
+
+#[test]
+fn gini_of_food_eaten() {
+    assert_eq!(gini(&[5.0, 5.0, 5.0, 5.0]), 0.0);
+    assert_eq!(gini(&[0.0, 0.0]), 0.0);
+    // one worm ate everything
+    assert!((gini(&[0.0, 0.0, 0.0, 8.0]) - 0.75).abs() < 1e-12);
+    assert!(gini(&[1.0, 2.0, 3.0]) < gini(&[0.0, 1.0, 5.0]));
+}
+
+#[test]
+fn weighted_sum_of_objectives() {
+    let rollout = Rollout {
+        food_eaten: 30.0,
+        lifetime: 400.0,
+        distance: 600.0,
+        fairness: 0.5,
+        survivors: 1.0,
+        extinction: 1.0,
+        behaviour: Behaviour::default(),
+    };
+    let terms: Vec<Term> = ["food", "efficiency:100", "lifetime:-0.01"].iter()
+        .map(|term| term.parse().unwrap())
+        .collect();
+    let fitness = Fitness { terms };
+
+    assert!((fitness.of(&rollout) - (30.0 + 5.0 - 4.0)).abs() < 1e-12);
+    assert_eq!(fitness.describe(), "food x1 + efficiency x100 + lifetime x-0.01");
+    assert!("speed:2".parse::<Term>().is_err());
+    assert!("food:lots".parse::<Term>().is_err());
+
+    let config = TrainConfig { iterations: 500, ..TrainConfig::default() };
+    let (lowest, highest) = fitness.range(&config);
+    assert_eq!(lowest, -5.0);
+    assert_eq!(highest, 101.0 * 1000.0);
+}
+
+#[test]
+fn starving_on_the_last_tick_is_extinction() {
+    let starving = |iterations| {
+        let rules = Rules { drain: 0.05, ..Rules::default() };
+        let worms = generate_worms(3, &rules, &mut seeded_rng(1));
+        play(World::new(rules, worms, Vec::new(), seeded_rng(1)), iterations, 0.5)
+    };
+
+    let long = starving(1000);
+    assert_eq!(long.survivors, 0.0);
+    let last_death = (long.extinction * 1000.0).round() as usize;
+
+    // the last worm starves in the last tick
+    let exact = starving(last_death + 1);
+    assert_eq!(exact.survivors, 0.0);
+    assert_eq!(exact.extinction, last_death as f64 / (last_death + 1) as f64);
+}
\ No newline at end of file
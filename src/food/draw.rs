@@ -1,14 +1,21 @@
+use raylib::color::Color;
 use raylib::drawing::{RaylibDraw, RaylibDrawHandle, RaylibMode2D};
 use crate::food::{Food, ray_of_food};
+use crate::sim::rules::Rules;
+
+const FOOD_COLOR: Color = Color::ROSYBROWN;
+
 pub fn draw_foods(
     d2d: &mut RaylibMode2D<RaylibDrawHandle>,
     foods: &Vec<Food>,
+    rules: &Rules,
     percentage_animation: f32 // from 0 to 1
 ) {
     for food in foods.iter() {
         draw_food(
             d2d,
             food,
+            rules,
             percentage_animation
         );
     }
@@ -17,12 +24,13 @@ pub fn draw_foods(
 fn draw_food(
     d: &mut RaylibMode2D<RaylibDrawHandle>,
     food: &Food,
+    rules: &Rules,
     percentage_animation: f32 // from 0 to 1
 ) {
     d.draw_circle(
         food.pos.x as i32,
         food.pos.y as i32,
-        ray_of_food(food),
-        food.color
+        ray_of_food(food, rules),
+        FOOD_COLOR
     );
 }
@@ -1,23 +1,22 @@
-use raylib::math::Vector2;
 use crate::food::Food;
-use crate::math::{rand_float, rand_int};
+use crate::math::{rand_float, SimRng, Vec2};
+use crate::sim::rules::Rules;
 
-pub fn generate_food(num_food: i32) -> Vec<Food> {
+pub fn generate_food(num_food: i32, rules: &Rules, rng: &mut SimRng) -> Vec<Food> {
 
     let mut food = Vec::new();
 
     for _ in 0..num_food {
-        let pos = Vector2::new(
-            rand_int(0, 800) as f32,
-            rand_int(0, 450) as f32
+        let pos = Vec2::new(
+            rand_float(rng, 0.0, rules.width),
+            rand_float(rng, 0.0, rules.height)
         );
 
-        let amount = rand_float(70.0, 100.0);
+        let amount = rand_float(rng, rules.food_amount.0, rules.food_amount.1);
 
         food.push(Food {
             pos,
             amount,
-            color: raylib::color::Color::ROSYBROWN
         });
     }
 
@@ -1,15 +1,15 @@
 pub mod draw;
 pub mod generate;
 
-use raylib::color::Color;
-use raylib::math::Vector2;
+use crate::math::Vec2;
+use crate::sim::rules::Rules;
 
+#[derive(Debug, Clone)]
 pub struct Food {
-    pub pos: Vector2,
-    pub color: Color,
+    pub pos: Vec2,
     pub amount: f32, // from 1 to 100
 }
 
-pub fn ray_of_food(food: &Food) -> f32 {
-    10.0 * food.amount.sqrt()
+pub fn ray_of_food(food: &Food, rules: &Rules) -> f32 {
+    rules.food_radius_scale * food.amount.sqrt()
 }
\ No newline at end of file
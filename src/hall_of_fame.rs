@@ -0,0 +1,110 @@
+use serde::{Serialize, Deserialize};
+use crate::fitness::Score;
+use crate::train::Genome;
+
+/**
+A brain worth keeping, with its scores.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Champion<G> {
+    pub brain: G,
+    #[serde(default)]
+    pub fitness: f64, // training fitness in the generation it was found in
+    #[serde(default)]
+    pub validation: Score, // what it was picked on
+    pub holdout: Score, // only reported, so it stays an unbiased estimate
+    pub generation: usize, // from 1
+}
+
+/**
+The `size` best distinct brains of a run by validation score, best first.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HallOfFame<G> {
+    size: usize,
+    entries: Vec<Champion<G>>,
+}
+
+impl<G: Genome> HallOfFame<G> {
+    pub fn new(size: usize) -> HallOfFame<G> {
+        HallOfFame {
+            size,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn best(&self) -> Option<&Champion<G>> {
+        self.entries.first()
+    }
+
+    pub fn entries(&self) -> &[Champion<G>] {
+        &self.entries
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /**
+    Where a new brain with this `validation` score would enter, after the ones that scored the same.
+     */
+    pub fn rank(&self, validation: f64) -> usize {
+        self.entries.iter()
+            .position(|entry| entry.validation.mean < validation)
+            .unwrap_or(self.entries.len())
+    }
+
+    /**
+    The entry with the same genes as `brain`, if it is already in.
+     */
+    pub fn find(&self, brain: &G) -> Option<&Champion<G>> {
+        let genes = brain.gene_values();
+        self.entries.iter().find(|entry| entry.brain.gene_values() == genes)
+    }
+
+    /**
+    Lets `champion` in if it beats the worst entry.
+     */
+    pub fn consider(&mut self, champion: Champion<G>) {
+        if self.find(&champion.brain).is_some() {
+            return;
+        }
+
+        let rank = self.rank(champion.validation.mean);
+        if rank < self.size {
+            self.entries.insert(rank, champion);
+            self.entries.truncate(self.size);
+        }
+    }
+}
+
+#[test]
+fn keeps_the_best_distinct_brains() {
+    use crate::brain::weighted::WormBrain;
+
+    let brain = |speed_factor| WormBrain::from_genes(&[0.5, speed_factor, 0.5, 1.0, 1.0, 0.0, 1.0, 0.0]);
+    let score = |mean| Score { mean, variance: 0.0 };
+    let champion = |speed_factor, validation, generation| Champion {
+        brain: brain(speed_factor),
+        fitness: 0.0,
+        validation: score(validation),
+        holdout: score(10.0 - validation), // must not matter
+        generation,
+    };
+
+    let mut hall = HallOfFame::new(3);
+    hall.consider(champion(1.0, 4.0, 1));
+    hall.consider(champion(1.5, 6.0, 1));
+    hall.consider(champion(1.0, 9.0, 2)); // same genes, already in
+    hall.consider(champion(2.0, 6.0, 2));
+    hall.consider(champion(2.5, 1.0, 3)); // worse than all three
+    hall.consider(champion(3.0, 5.0, 3));
+
+    let ranked: Vec<(f64, usize)> = hall.entries().iter()
+        .map(|entry| (entry.brain.speed_factor, entry.generation))
+        .collect();
+    assert_eq!(ranked, vec![(1.5, 1), (2.0, 2), (3.0, 3)]);
+    assert_eq!(hall.rank(6.0), 2);
+    assert_eq!(hall.find(&brain(2.0)).map(|entry| entry.validation), Some(score(6.0)));
+    assert!(hall.find(&brain(1.0)).is_none());
+}
\ No newline at end of file
@@ -0,0 +1,83 @@
+use raylib::color::Color;
+use raylib::drawing::{RaylibDraw, RaylibDrawHandle};
+use crate::evolve::Evolution;
+use crate::worm::Worm;
+
+const NOTICE_SEC: f64 = 3.0;
+
+/**
+A message shown on top of the world for a few seconds.
+ */
+pub struct Notice {
+    pub text: String,
+    pub is_error: bool,
+    pub since: f64, // time it was raised
+}
+
+impl Notice {
+    pub fn info(text: String, now: f64) -> Notice {
+        Notice { text, is_error: false, since: now }
+    }
+
+    pub fn error(text: String, now: f64) -> Notice {
+        Notice { text, is_error: true, since: now }
+    }
+}
+
+pub fn draw_notice(d: &mut RaylibDrawHandle, notice: &Option<Notice>, now: f64) {
+    let Some(notice) = notice else {
+        return;
+    };
+
+    if now - notice.since > NOTICE_SEC {
+        return;
+    }
+
+    let color = if notice.is_error {
+        Color::new(255, 118, 117, 255)
+    } else {
+        Color::new(85, 239, 196, 255)
+    };
+
+    d.draw_text(&notice.text, 10, d.get_screen_height() - 30, 20, color);
+}
+
+/**
+Shows the state and the genome of the clicked worm.
+ */
+pub fn draw_worm_panel(d: &mut RaylibDrawHandle, worm: &Worm) {
+    let mut lines = vec![
+        format!("Worm #{}{}", worm.id, if worm.life > 0.0 { "" } else { " (dead)" }),
+        format!("Life: {:.2}", worm.life),
+        format!("Speed: {:.2}", worm.speed),
+        format!("Food eaten: {:.1}", worm.food_eaten),
+    ];
+
+    match &worm.brain {
+        Some(brain) => lines.extend(brain.describe()),
+        None => lines.push("No brain".to_string()),
+    }
+
+    let line_height = 20;
+    d.draw_rectangle(5, 5, 230, line_height * lines.len() as i32 + 10, Color::new(0, 0, 0, 150));
+
+    for (i, line) in lines.iter().enumerate() {
+        d.draw_text(line, 10, 10 + line_height * i as i32, 16, Color::WHITE);
+    }
+}
+
+pub fn draw_evolution_stats(d: &mut RaylibDrawHandle, evolution: &Evolution) {
+    let lines = [
+        format!("Generation: {}", evolution.generation),
+        format!("Best fitness: {:.1}", evolution.best_fitness),
+        format!("Mean fitness: {:.1}", evolution.mean_fitness),
+    ];
+
+    let x = d.get_screen_width() - 210;
+    let line_height = 20;
+    d.draw_rectangle(x - 5, 5, 210, line_height * lines.len() as i32 + 10, Color::new(0, 0, 0, 150));
+
+    for (i, line) in lines.iter().enumerate() {
+        d.draw_text(line, x, 10 + line_height * i as i32, 16, Color::WHITE);
+    }
+}
\ No newline at end of file
@@ -4,26 +4,47 @@ mod control;
 mod worm;
 mod food;
 mod train;
+mod brain;
+mod sim;
+mod render;
+mod cli;
+mod reload;
+mod hud;
+mod evolve;
+mod operators;
+mod fitness;
+mod checkpoint;
+mod metrics;
+mod hall_of_fame;
+mod cma;
+mod map_elites;
+mod sweep;
+mod evaluate;
 
-use std::fs;
-use std::ops::{Add, Deref, Sub};
 use raylib::camera::Camera2D;
 use raylib::color::Color;
-use raylib::drawing::{RaylibDraw, RaylibDrawHandle, RaylibMode2D, RaylibMode2DExt};
+use raylib::consts::MouseButton;
+use raylib::drawing::{RaylibDraw, RaylibMode2DExt};
 use raylib::math::Vector2;
 use crate::control::handle_controls;
-use crate::food::draw::draw_foods;
-use crate::food::Food;
-use crate::food::generate::generate_food;
 use crate::map::draw_background;
-use crate::math::from_angle_to_vec2;
-use crate::train::WormModel;
-use crate::worm::{Worm};
-use crate::worm::draw::{draw_worms};
-use crate::worm::generate::generate_worms;
-use crate::worm::r#move::move_worms;
+use crate::math::seeded_rng;
+use crate::render::{draw_world, to_vector2};
+use crate::sim::rules::Rules;
+use crate::sim::World;
+use crate::evolve::Evolution;
+use crate::hud::{draw_evolution_stats, draw_notice, draw_worm_panel, Notice};
+use crate::reload::{ModelWatcher, Reload};
+use crate::brain::{builtin, share, SharedBrain};
+use crate::brain::neural::NeuralBrain;
+use crate::brain::weighted::WormBrain;
+use crate::checkpoint::{catch_interrupt, checkpoint_header, interrupted, load_checkpoint};
+use crate::evaluate::{evaluate, mann_whitney, Estimate, Evaluation};
+use crate::fitness::{describe_score, sample_episodes, Episode};
+use crate::map_elites::{map_elites, render_heatmap, ArchiveModel};
+use crate::sweep::{configurations, load_spec, write_summary, SweepResult};
+use crate::train::{benchmark, load_archive, load_model, resume, save_model, train, train_from, BrainKind, Genome, Metadata, ModelFile, PopulationModel, TrainConfig, WormModel};
 use crate::worm::search::highlight_selected_worm;
-use crate::worm::starve::{feed_worms, starve_worms};
 
 const EASING_SEC: f64 = 0.5;
 const WIDTH: i32 = 800;
@@ -31,15 +52,382 @@ const HEIGHT: i32 = 800;
 
 fn main()
 {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("train") => run_train(&args[1..]),
+        Some("benchmark") => run_benchmark(&args[1..]),
+        Some("map-elites") => run_map_elites(&args[1..]),
+        Some("sweep") => run_sweep(&args[1..]),
+        Some("evaluate") => run_evaluate(&args[1..]),
+        Some(command) if !command.starts_with("--") => {
+            eprintln!("unknown subcommand: {}\n{}", command, cli::USAGE);
+            std::process::exit(2);
+        }
+        _ => run_viewer(&args),
+    }
+}
+
+fn run_train(args: &[String]) {
+    let resume_from = cli::flag_or_exit::<String>(args, "--resume");
+    let config = match &resume_from {
+        // a resumed run keeps the settings it was started with
+        Some(path) => checkpoint_header(path)
+            .map_err(|e| format!("Failed to load checkpoint {}: {}", path, e))
+            .and_then(|header| cli::parse_resume_args(args, header.config, header.generation)),
+        None => cli::parse_train_args(args),
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    catch_interrupt();
+
+    match config.brain {
+        BrainKind::Weighted => train_and_save::<WormBrain>(&config, resume_from.as_deref()),
+        BrainKind::Neural => train_and_save::<NeuralBrain>(&config, resume_from.as_deref()),
+    }
+}
+
+fn train_and_save<G: Genome>(config: &TrainConfig, resume_from: Option<&str>) {
+    let (model, population) = match resume_from {
+        Some(path) => {
+            let mut checkpoint = load_checkpoint::<G>(path).unwrap_or_else(|e| {
+                eprintln!("Failed to load checkpoint {}: {}", path, e);
+                std::process::exit(1);
+            });
+            checkpoint.config = config.clone();
+            println!("Resuming {} at generation {}/{}", path, checkpoint.generation + 1, config.generations);
+            resume(checkpoint)
+        }
+        None if config.seed_from.is_some() => train_from::<G>(config, load_seeds(config)),
+        None => train::<G>(config),
+    };
+
+    if interrupted() {
+        if let Some(path) = &config.checkpoint {
+            println!("Interrupted, continue with: train --resume {}", path);
+        }
+        std::process::exit(130);
+    }
+
+    if let Err(e) = save_model(&model, &config.output) {
+        eprintln!("Failed to save model to {}: {}", config.output, e);
+        std::process::exit(1);
+    }
+    println!("Model saved to {} with fitness: {:.2}", config.output, model.fitness);
+
+    if let Some(path) = &config.population_output {
+        if let Err(e) = save_model(&population, path) {
+            eprintln!("Failed to save population to {}: {}", path, e);
+            std::process::exit(1);
+        }
+        println!("Population of {} saved to {}", population.brains.len(), path);
+    }
+}
+
+/**
+The hall of fame of `config.seed_from`, if any, to start the population with.
+ */
+fn load_seeds<G: Genome>(config: &TrainConfig) -> Vec<G> {
+    let Some(path) = &config.seed_from else {
+        return Vec::new();
+    };
+
+    let seeds = load_archive::<G>(path).unwrap_or_else(|e| {
+        eprintln!("Failed to load {}: {}", path, e);
+        std::process::exit(1);
+    });
+    println!("Seeding the population with {} brains from {}", seeds.len(), path);
+    seeds
+}
+
+fn run_map_elites(args: &[String]) {
+    let map_args = cli::parse_map_elites_args(args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
+    catch_interrupt();
+
+    match map_args.config.brain {
+        BrainKind::Weighted => map_and_save::<WormBrain>(&map_args),
+        BrainKind::Neural => map_and_save::<NeuralBrain>(&map_args),
+    }
+}
+
+/**
+Saves the archive, its fittest elite as the model, every elite as the population if asked,
+and the heatmap if asked.
+ */
+fn map_and_save<G: Genome>(map_args: &cli::MapElitesArgs) {
+    let config = &map_args.config;
+    let archive = map_elites::<G>(config, map_args.bins, load_seeds(config));
+    let Some(best) = archive.best() else {
+        eprintln!("No elite found");
+        std::process::exit(1);
+    };
+
+    let metadata = Metadata {
+        generation: best.generation,
+        fitness: format!("{}, mean over {} fixed episodes of {} ticks", describe_score(config), config.episodes, config.iterations),
+        config: config.clone(),
+    };
+    let model = WormModel::new(best.brain.clone(), best.fitness, Some(metadata.clone()));
+    let population = PopulationModel::new(
+        archive.elites.iter().map(|elite| elite.brain.clone()).collect(),
+        Some(metadata.clone())
+    );
+
+    if let Err(e) = save_model(&model, &config.output) {
+        eprintln!("Failed to save model to {}: {}", config.output, e);
+        std::process::exit(1);
+    }
+    println!("Best elite saved to {} with fitness: {:.2}", config.output, model.fitness);
+
+    if let Some(path) = &config.population_output {
+        if let Err(e) = save_model(&population, path) {
+            eprintln!("Failed to save population to {}: {}", path, e);
+            std::process::exit(1);
+        }
+        println!("{} elites saved to {}", population.brains.len(), path);
+    }
+
+    if let Some(path) = &map_args.heatmap {
+        if let Err(e) = render_heatmap(&archive, path) {
+            eprintln!("Failed to save heatmap: {}", e);
+            std::process::exit(1);
+        }
+        println!("Heatmap saved to {}", path);
+    }
+
+    let num_elites = archive.elites.len();
+    if let Err(e) = save_model(&ArchiveModel::new(archive, metadata), &map_args.archive) {
+        eprintln!("Failed to save archive to {}: {}", map_args.archive, e);
+        std::process::exit(1);
+    }
+    println!("Archive of {} elites saved to {}", num_elites, map_args.archive);
+}
+
+fn run_sweep(args: &[String]) {
+    let sweep_args = cli::parse_sweep_args(args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let spec = load_spec(&sweep_args.spec).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let configurations = configurations(&spec).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
+    catch_interrupt();
+
+    let runs = configurations.len() * spec.seeds.len();
+    println!("Sweeping {} configurations over {} seeds", configurations.len(), spec.seeds.len());
+    let mut results = Vec::new();
+    let mut run = 0;
+    'sweep: for configuration in configurations {
+        let episodes = spec.evaluation(&configuration.config);
+        let mut result = SweepResult { configuration, scores: Vec::new() };
+        for &seed in &spec.seeds {
+            let config = TrainConfig {
+                seed,
+                quiet: true,
+                checkpoint: None, // an interrupted sweep would overwrite the checkpoint of a training run
+                metrics: None,
+                ..result.configuration.config.clone()
+            };
+            let score = match config.brain {
+                BrainKind::Weighted => sweep_score::<WormBrain>(&config, &episodes),
+                BrainKind::Neural => sweep_score::<NeuralBrain>(&config, &episodes),
+            };
+            if interrupted() {
+                break 'sweep;
+            }
+            run += 1;
+            println!("Run {}/{}: {} (seed {}): score {:.2}", run, runs, result.configuration.describe(), seed, score);
+            result.scores.push((seed, score));
+        }
+        results.push(result);
+    }
+    if interrupted() {
+        println!("Interrupted, ranking the {} configurations done so far", results.len());
+    }
+
+    results.sort_by(|a, b| b.mean().total_cmp(&a.mean()));
+
+    println!("{:>4} {:>14} {:>10}  Settings", "Rank", "Mean score", "Std");
+    for (rank, result) in results.iter().enumerate() {
+        println!("{:>4} {:>14.2} {:>10.2}  {}", rank + 1, result.mean(), result.std(), result.configuration.describe());
+    }
+
+    if let Some(path) = &sweep_args.summary {
+        if let Err(e) = write_summary(path, &results) {
+            eprintln!("Failed to save summary to {}: {}", path, e);
+            std::process::exit(1);
+        }
+        println!("Summary saved to {}", path);
+    }
+}
+
+/**
+Mean fitness over `episodes` of the brain a whole training run ends with.
+ */
+fn sweep_score<G: Genome>(config: &TrainConfig, episodes: &[Episode]) -> f64 {
+    let (model, _) = train_from::<G>(config, load_seeds(config));
+    evaluate(&share(&model.brain), config, episodes).fitness.mean
+}
+
+fn run_benchmark(args: &[String]) {
+    let bench = match cli::parse_benchmark_args(args) {
+        Ok(bench) => bench,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let brains = named_brains(&bench.models, &bench.strategies);
+    let mut results = benchmark(&brains, &bench.config, bench.episodes);
+    results.sort_by(|a, b| b.1.mean.total_cmp(&a.1.mean));
+
+    println!("{:<40} {:>12} {:>12}", "Brain", "Mean fitness", "Variance");
+    for (name, score) in results {
+        println!("{:<40} {:>12.2} {:>12.2}", name, score.mean, score.variance);
+    }
+}
+
+/**
+The brains of the model files, named after their file (and index in a population),
+then the built-in strategies.
+ */
+fn named_brains(models: &[String], strategies: &[String]) -> Vec<(String, SharedBrain)> {
+    let mut brains: Vec<(String, SharedBrain)> = Vec::new();
+    for path in models {
+        let model = load_model(path).unwrap_or_else(|e| {
+            eprintln!("Failed to load {}: {}", path, e);
+            std::process::exit(1);
+        });
+        let model_brains = model.brains();
+        let single = model_brains.len() == 1;
+        for (i, brain) in model_brains.into_iter().enumerate() {
+            let name = if single { path.clone() } else { format!("{}[{}]", path, i) };
+            brains.push((name, brain));
+        }
+    }
+    for name in strategies {
+        let brain = builtin(name).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        });
+        brains.push((name.clone(), brain));
+    }
+    brains
+}
+
+fn run_evaluate(args: &[String]) {
+    let eval = cli::parse_evaluate_args(args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let brains = named_brains(&eval.models, &eval.strategies);
+    if brains.len() > 2 {
+        eprintln!("evaluate compares at most two brains, got {} (a population file holds one per worm)", brains.len());
+        std::process::exit(2);
+    }
+
+    let config = &eval.config;
+    let episodes = sample_episodes(eval.episodes, &config.rules, config.num_food, &mut seeded_rng(config.seed));
+    println!("{} episodes of {} ticks, seed {}, fitness: {}", eval.episodes, config.iterations, config.seed, describe_score(config));
+    println!("{:<40} {:>20} {:>20} {:>20}", "Brain", "Fitness (95% CI)", "Lifetime (95% CI)", "Food eaten (95% CI)");
+    let evaluations: Vec<(String, Evaluation)> = brains.iter()
+        .map(|(name, brain)| (name.clone(), evaluate(brain, config, &episodes)))
+        .collect();
+    for (name, evaluation) in &evaluations {
+        let interval = |estimate: Estimate| format!("{:.2} ± {:.2}", estimate.mean, estimate.margin);
+        println!(
+            "{:<40} {:>20} {:>20} {:>20}",
+            name, interval(evaluation.fitness), interval(evaluation.lifetime), interval(evaluation.food_eaten)
+        );
+    }
+
+    if let [(name_a, a), (name_b, b)] = evaluations.as_slice() {
+        let test = mann_whitney(&a.scores, &b.scores);
+        println!(
+            "Mann-Whitney U = {:.1}, {} beats {} in {:.1}% of the episode pairs, p = {:.4}",
+            test.u, name_a, name_b, test.superiority * 100.0, test.p_value
+        );
+        if test.p_value < 0.05 {
+            let better = if test.superiority > 0.5 { name_a } else { name_b };
+            println!("{} is significantly better (at 5%)", better);
+        } else {
+            println!("No significant difference (at 5%)");
+        }
+    }
+}
+
+fn run_viewer(args: &[String]) {
+    if let Err(e) = cli::check_viewer_args(args) {
+        eprintln!("{}\n{}", e, cli::USAGE);
+        std::process::exit(2);
+    }
+    let seed = cli::flag_or_exit::<u64>(args, "--seed").unwrap_or_else(rand::random);
+    println!("Seed: {}", seed);
+
+    let model_path = cli::flag_or_exit(args, "--model")
+        .unwrap_or_else(|| "worm_model.json".to_string());
+    let evolve_output = cli::flag_or_exit(args, "--evolve-output")
+        .unwrap_or_else(|| "evolved_model.json".to_string());
+    let evolve = args.iter().any(|arg| arg == "--evolve");
+    let strategy = cli::flag_or_exit::<String>(args, "--strategy");
+    let watch_model = !evolve && strategy.is_none();
+    let hall_of_fame = args.iter().any(|arg| arg == "--hall-of-fame");
+
     let (mut rl, thread) = raylib::init()
-        .size(800, 800)
+        .size(WIDTH, HEIGHT)
         .title("Training Worms")
         .vsync()
         .build();
 
+    // the arena the model was trained in, so its brain plays by the same rules
+    let model = load_model(&model_path).ok();
+    let (rules, num_food) = match model.as_ref().and_then(ModelFile::metadata) {
+        Some(metadata) => (metadata.config.rules.clone(), metadata.config.num_food),
+        None => (Rules::default(), 10),
+    };
     let num_worms = 100;
-    let mut worms: Vec<Worm> = generate_worms(num_worms);
-    let mut food: Vec<Food> = generate_food(10);
+    let mut world = World::random(rules.clone(), num_worms, num_food, seed);
+
+    // the model is loaded once here, then only when the file changes
+    let mut watcher = ModelWatcher::new(&model_path);
+    let mut notice: Option<Notice> = None;
+    reload_brain(&mut watcher, &mut world, &mut notice, hall_of_fame, rl.get_time());
+
+    // a built-in strategy replaces the model
+    if let Some(name) = &strategy {
+        let brain = builtin(name).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        });
+        world.assign_brains(&[brain]);
+    }
+
+    // in evolution mode the viewer breeds its own brains instead of reloading the model
+    let mut evolution = if evolve {
+        let initial = model.map(|model| model.weighted_brains()).unwrap_or_default();
+        let evolution = Evolution::new(num_worms, rules, num_food, initial, &evolve_output, seed);
+        evolution.assign_brains(&mut world);
+        Some(evolution)
+    } else {
+        None
+    };
 
     let mut prev_time = rl.get_time();
 
@@ -53,6 +441,7 @@ fn main()
 
     let mut prev_mouse_pos = Vector2::zero();
     let mut focus_target: Option<Vector2> = None;
+    let mut selected_worm: Option<usize> = None;
 
     while !rl.window_should_close() {
 
@@ -71,78 +460,81 @@ fn main()
         let current_time = d2d.get_time();
         let delta_time = current_time - prev_time;
 
-        draw_foods(
+        draw_world(
             &mut d2d,
-            &food,
-            (delta_time / EASING_SEC) as f32
-        );
-
-        draw_worms(
-            &mut d2d,
-            &worms,
+            &world,
             (delta_time / EASING_SEC) as f32
         );
 
         if let Some(worm) = highlight_selected_worm(
-            &worms,
+            &world.worms,
             &mut d2d,
             &camera
         ) {
-            focus_target = Some(worm.pos);
+            focus_target = Some(to_vector2(worm.pos));
+
+            if d2d.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+                selected_worm = Some(worm.id);
+            }
         }
 
         // if delta time is succeeded, move the worms
-        if(current_time - prev_time > EASING_SEC) {
+        if current_time - prev_time > EASING_SEC {
             prev_time = current_time;
-            change_worms_direction(&mut worms, &food);
-            move_worms(&mut worms);
-            starve_worms(&mut worms);
-            feed_worms(&mut worms, &mut food);
-        }
+            if watch_model {
+                reload_brain(&mut watcher, &mut world, &mut notice, hall_of_fame, current_time);
+            }
+            world.step(1.0);
 
-    }
-}
-
-fn change_worms_direction(worms: &mut Vec<Worm>, foods: &Vec<Food>) {
-    // Load and parse the model only once
-    let model_json = match fs::read_to_string("worm_model.json") {
-        Ok(content) => content,
-        Err(e) => {
-            eprintln!("Error reading model file: {}", e);
-            return;
+            if let Some(evolution) = &mut evolution {
+                if world.worms.is_empty() {
+                    world = evolution.next_generation(&world);
+                    notice = Some(match evolution.save_best() {
+                        Ok(()) => Notice::info(
+                            format!("Generation {}, best genome saved to {}", evolution.generation, evolution.output),
+                            current_time
+                        ),
+                        Err(e) => Notice::error(format!("Failed to save {}: {}", evolution.output, e), current_time),
+                    });
+                }
+            }
         }
-    };
 
-    let model: WormModel = match serde_json::from_str(&model_json) {
-        Ok(m) => m,
-        Err(e) => {
-            eprintln!("Error parsing model JSON: {}", e);
-            return;
+        drop(d2d);
+        draw_notice(&mut d, &notice, current_time);
+        if let Some(worm) = selected_worm.and_then(|id| world.find_worm(id)) {
+            draw_worm_panel(&mut d, worm);
         }
-    };
-
-    let foods_models = foods.iter().map(|food| {
-        train::Food {
-            position: (food.pos.x as i32, food.pos.y as i32),
-            value: food.amount as i32,
+        if let Some(evolution) = &evolution {
+            draw_evolution_stats(&mut d, evolution);
         }
-    }).collect::<Vec<_>>();
-
-    // Update each worm's direction using the model's brain
-    for worm in worms.iter_mut() {
-        let direction = model.brain.calculate_direction(
-            (worm.pos.x as f64, worm.pos.y as f64),
-            foods_models.deref(),
-        );
-
-        // Store the calculated direction (in radians) in the worm
-        worm.dir = from_angle_to_vec2(direction as f32);
+    }
+}
 
-        // If the worm's life is below threshold, increase speed
-        if worm.life < model.brain.life_threshold as f32 {
-            worm.speed = (model.brain.speed_factor * 1.5) as f32;
-        } else {
-            worm.speed = model.brain.speed_factor as f32;
+/**
+Swaps in the model file if it changed since the last poll,
+a rejected file keeps the current brain.
+With `hall_of_fame` the worms share out the brains of the model's hall of fame.
+ */
+fn reload_brain(watcher: &mut ModelWatcher, world: &mut World, notice: &mut Option<Notice>, hall_of_fame: bool, now: f64) {
+    match watcher.poll() {
+        Reload::Unchanged => {}
+        Reload::Loaded(model) => {
+            let brains = if hall_of_fame { model.archive_brains() } else { model.brains() };
+            world.assign_brains(&brains);
+            let summary = match model.as_ref() {
+                ModelFile::Single(_) if hall_of_fame => format!("hall of fame of {}", brains.len()),
+                ModelFile::Single(model) => format!("fitness {:.1}", model.fitness),
+                ModelFile::Population(population) => format!("{} brains", population.brains.len()),
+                ModelFile::Neural(_) if hall_of_fame => format!("neural hall of fame of {}", brains.len()),
+                ModelFile::Neural(model) => format!("neural, fitness {:.1}", model.fitness),
+                ModelFile::NeuralPopulation(population) => format!("{} neural brains", population.brains.len()),
+            };
+            *notice = Some(Notice::info(format!("Loaded {} ({})", watcher.path, summary), now));
+        }
+        Reload::Rejected(e) => {
+            eprintln!("Error loading model: {}", e);
+            *notice = Some(Notice::error(format!("Rejected {}", e), now));
         }
     }
 }
\ No newline at end of file
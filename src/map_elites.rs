@@ -0,0 +1,260 @@
+use rand::Rng;
+use raylib::color::Color;
+use raylib::texture::Image;
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::brain::share;
+use crate::checkpoint::interrupted;
+use crate::fitness::{episode_rollouts, sample_episodes, Behaviour, Episode, Fitness};
+use crate::math::seeded_rng;
+use crate::operators::{CrossoverKind, Mutator};
+use crate::train::{with_threads, Genome, Metadata, TrainConfig, MODEL_VERSION};
+
+/**
+Range of each behaviour descriptor, cut in even bins. Anything past it lands in the edge bins.
+ */
+pub const SPEED_RANGE: (f64, f64) = (0.0, 4.5); // up to the fastest neural worm
+pub const HUNGER_RANGE: (f64, f64) = (0.0, 1.0);
+
+const CELL_PIXELS: i32 = 40;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Elite<G> {
+    pub cell: (usize, usize), // speed bin, hunger bin
+    pub brain: G,
+    pub fitness: f64, // mean score per episode
+    pub behaviour: Behaviour,
+    pub generation: usize, // from 1
+}
+
+/**
+The fittest brain found for each kind of behaviour, on a grid of `bins` speeds by `bins` hungers.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Archive<G> {
+    pub bins: usize,
+    pub elites: Vec<Elite<G>>,
+}
+
+impl<G> Archive<G> {
+    pub fn new(bins: usize) -> Archive<G> {
+        Archive {
+            bins,
+            elites: Vec::new(),
+        }
+    }
+
+    pub fn cell_of(&self, behaviour: &Behaviour) -> (usize, usize) {
+        (bin(behaviour.speed, SPEED_RANGE, self.bins), bin(behaviour.hunger, HUNGER_RANGE, self.bins))
+    }
+
+    /**
+    Keeps `brain` if its cell is empty or holds a less fit elite, true if it was kept.
+     */
+    pub fn insert(&mut self, brain: G, fitness: f64, behaviour: Behaviour, generation: usize) -> bool {
+        let cell = self.cell_of(&behaviour);
+        let elite = Elite { cell, brain, fitness, behaviour, generation };
+
+        match self.elites.iter_mut().find(|elite| elite.cell == cell) {
+            Some(current) if current.fitness >= fitness => false,
+            Some(current) => {
+                *current = elite;
+                true
+            }
+            None => {
+                self.elites.push(elite);
+                true
+            }
+        }
+    }
+
+    pub fn best(&self) -> Option<&Elite<G>> {
+        self.elites.iter().max_by(|a, b| a.fitness.total_cmp(&b.fitness))
+    }
+
+    /**
+    Share of the cells holding an elite.
+     */
+    pub fn coverage(&self) -> f64 {
+        self.elites.len() as f64 / (self.bins * self.bins) as f64
+    }
+
+    /**
+    Fitness summed over the elites, grows with both quality and diversity.
+     */
+    pub fn qd_score(&self) -> f64 {
+        self.elites.iter().map(|elite| elite.fitness).sum()
+    }
+}
+
+fn bin(value: f64, (low, high): (f64, f64), bins: usize) -> usize {
+    let position = ((value - low) / (high - low) * bins as f64).floor().max(0.0) as usize;
+    position.min(bins - 1)
+}
+
+/**
+An archive as saved to disk, with the ranges its bins cut.
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ArchiveModel<G> {
+    pub(crate) version: u64,
+    pub(crate) speed_range: (f64, f64),
+    pub(crate) hunger_range: (f64, f64),
+    pub(crate) archive: Archive<G>,
+    pub(crate) metadata: Metadata,
+}
+
+impl<G> ArchiveModel<G> {
+    pub fn new(archive: Archive<G>, metadata: Metadata) -> ArchiveModel<G> {
+        ArchiveModel {
+            version: MODEL_VERSION,
+            speed_range: SPEED_RANGE,
+            hunger_range: HUNGER_RANGE,
+            archive,
+            metadata,
+        }
+    }
+}
+
+/**
+MAP-Elites: a batch of `population_size` random brains (or `seeds`), then every generation
+as many children of random elites, each kept if it beats the elite of its cell.
+Every brain plays the same `episodes` episodes, so elites of different generations compare fairly.
+Brains are evaluated on `config.threads` workers.
+ */
+pub fn map_elites<G: Genome>(config: &TrainConfig, bins: usize, seeds: Vec<G>) -> Archive<G> {
+    with_threads(config.threads, || search(config, bins, seeds))
+}
+
+fn search<G: Genome>(config: &TrainConfig, bins: usize, seeds: Vec<G>) -> Archive<G> {
+    let mut rng = seeded_rng(config.seed);
+    let mut episode_rng = seeded_rng(config.seed);
+    episode_rng.set_stream(2);
+    let episodes = sample_episodes(config.episodes, &config.rules, config.num_food, &mut episode_rng);
+    let mutator = Mutator::new(config);
+
+    let mut archive = Archive::new(bins);
+    let mut batch = seeds;
+    batch.truncate(config.population_size);
+    while batch.len() < config.population_size {
+        batch.push(G::random(&mut rng));
+    }
+
+    for generation in 1..=config.generations {
+        if generation > 1 {
+            batch = (0..config.population_size)
+                .map(|_| child(&archive, config, &mutator, &mut rng))
+                .collect();
+        }
+
+        let evaluated: Vec<(f64, Behaviour)> = batch.par_iter()
+            .map(|brain| evaluate(brain, config, &episodes))
+            .collect();
+        let mut kept = 0;
+        for (brain, (fitness, behaviour)) in batch.drain(..).zip(evaluated) {
+            if archive.insert(brain, fitness, behaviour, generation) {
+                kept += 1;
+            }
+        }
+
+        println!(
+            "Generation {}/{}: {} new elites, coverage {:.0}%, best {:.1}, QD score {:.0}",
+            generation,
+            config.generations,
+            kept,
+            archive.coverage() * 100.0,
+            archive.best().map_or(0.0, |best| best.fitness),
+            archive.qd_score()
+        );
+
+        if interrupted() {
+            break;
+        }
+    }
+
+    archive
+}
+
+/**
+Mean score and behaviour of `brain` over the episodes.
+ */
+fn evaluate<G: Genome>(brain: &G, config: &TrainConfig, episodes: &[Episode]) -> (f64, Behaviour) {
+    let rollouts = episode_rollouts(&share(brain), brain.hunger_threshold(), config, episodes);
+    let objectives = Fitness::from_config(config);
+    let fitness = rollouts.iter().map(|rollout| objectives.of(rollout)).sum::<f64>() / rollouts.len().max(1) as f64;
+    let behaviours: Vec<Behaviour> = rollouts.iter().map(|rollout| rollout.behaviour).collect();
+    (fitness, Behaviour::mean(&behaviours))
+}
+
+fn child<G: Genome, R: Rng>(archive: &Archive<G>, config: &TrainConfig, mutator: &Mutator, rng: &mut R) -> G {
+    let parent1 = &archive.elites[rng.gen_range(0..archive.elites.len())].brain;
+    let parent2 = &archive.elites[rng.gen_range(0..archive.elites.len())].brain;
+
+    let mut child = match config.crossover {
+        CrossoverKind::Uniform => parent1.crossover(parent2, rng),
+        CrossoverKind::Blend => parent1.blend(parent2, config.blend_alpha, config.bounds, rng),
+    };
+    child.mutate(mutator, rng);
+    child
+}
+
+/**
+The elite grid as an image: speed grows to the right, hunger upwards,
+fitter elites are brighter and empty cells stay dark.
+ */
+pub fn render_heatmap<G>(archive: &Archive<G>, path: &str) -> Result<(), String> {
+    let size = archive.bins as i32 * CELL_PIXELS;
+    let mut image = Image::gen_image_color(size, size, Color::new(45, 52, 54, 255));
+
+    let lowest = archive.elites.iter().map(|elite| elite.fitness).fold(f64::INFINITY, f64::min);
+    let highest = archive.elites.iter().map(|elite| elite.fitness).fold(f64::NEG_INFINITY, f64::max);
+
+    for elite in &archive.elites {
+        let heat = if highest > lowest { (elite.fitness - lowest) / (highest - lowest) } else { 1.0 };
+        let x = elite.cell.0 as i32 * CELL_PIXELS;
+        let y = size - (elite.cell.1 as i32 + 1) * CELL_PIXELS;
+        image.draw_rectangle(x + 1, y + 1, CELL_PIXELS - 2, CELL_PIXELS - 2, heat_color(heat));
+    }
+
+    if image.export_image(path) {
+        Ok(())
+    } else {
+        Err(format!("failed to write {}", path))
+    }
+}
+
+/**
+Dark blue for 0, through red, to yellow for 1.
+ */
+fn heat_color(heat: f64) -> Color {
+    let mix = |from: f64, to: f64, t: f64| (from + (to - from) * t).round() as u8;
+    if heat < 0.5 {
+        let t = heat * 2.0;
+        Color::new(mix(30.0, 220.0, t), mix(40.0, 50.0, t), mix(120.0, 50.0, t), 255)
+    } else {
+        let t = (heat - 0.5) * 2.0;
+        Color::new(mix(220.0, 250.0, t), mix(50.0, 230.0, t), mix(50.0, 60.0, t), 255)
+    }
+}
+
+#[test]
+fn keeps_the_fittest_brain_of_each_cell() {
+    let mut archive = Archive::new(4);
+    let slow_and_full = Behaviour { speed: 0.2, hunger: 0.1 };
+    let fast_and_hungry = Behaviour { speed: 9.0, hunger: 1.0 }; // past the range, in the last bins
+
+    assert_eq!(archive.cell_of(&slow_and_full), (0, 0));
+    assert_eq!(archive.cell_of(&fast_and_hungry), (3, 3));
+    assert_eq!(archive.cell_of(&Behaviour { speed: 2.3, hunger: 0.5 }), (2, 2));
+
+    assert!(archive.insert("a", 5.0, slow_and_full, 1));
+    assert!(!archive.insert("b", 4.0, slow_and_full, 2));
+    assert!(!archive.insert("c", 5.0, slow_and_full, 2)); // a tie keeps the elder
+    assert!(archive.insert("d", 7.0, slow_and_full, 3));
+    assert!(archive.insert("e", 1.0, fast_and_hungry, 3));
+
+    assert_eq!(archive.elites.len(), 2);
+    assert_eq!(archive.best().map(|best| best.brain), Some("d"));
+    assert_eq!(archive.coverage(), 2.0 / 16.0);
+    assert_eq!(archive.qd_score(), 8.0);
+}
\ No newline at end of file
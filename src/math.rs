@@ -1,39 +1,70 @@
-use rand::Rng;
-use raylib::math::Vector2;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
-pub fn rand_int(min: i32, max: i32) -> i32 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(min..max)
+/**
+Every random decision of a run goes through this generator,
+so the same seed always replays the same run.
+ */
+pub type SimRng = ChaCha8Rng;
+
+pub fn seeded_rng(seed: u64) -> SimRng {
+    SimRng::seed_from_u64(seed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vec2 {
+    pub fn new(x: f32, y: f32) -> Vec2 {
+        Vec2 { x, y }
+    }
+
+    pub fn length(&self) -> f32 {
+        (self.x * self.x + self.y * self.y).sqrt()
+    }
+
+    pub fn distance_to(&self, other: Vec2) -> f32 {
+        Vec2::new(other.x - self.x, other.y - self.y).length()
+    }
+
+    pub fn lerp(&self, other: Vec2, amount: f32) -> Vec2 {
+        Vec2::new(
+            self.x + (other.x - self.x) * amount,
+            self.y + (other.y - self.y) * amount,
+        )
+    }
 }
 
-pub fn rand_float(min: f32, max: f32) -> f32 {
-    let mut rng = rand::thread_rng();
+pub fn rand_float<R: Rng>(rng: &mut R, min: f32, max: f32) -> f32 {
     rng.gen_range(min..max)
 }
 
-pub fn add_vec2(a: Vector2, b: Vector2, mul: f32) -> Vector2 {
-    Vector2::new(
+pub fn add_vec2(a: Vec2, b: Vec2, mul: f32) -> Vec2 {
+    Vec2::new(
         a.x + b.x * mul,
         a.y + b.y * mul,
     )
 }
 
-pub fn sub_vec2(a: Vector2, b: Vector2, mul: f32) -> Vector2 {
-    Vector2::new(
+pub fn sub_vec2(a: Vec2, b: Vec2, mul: f32) -> Vec2 {
+    Vec2::new(
         a.x - b.x * mul,
         a.y - b.y * mul,
     )
 }
 
-pub fn vector_between(a: Vector2, b: Vector2) -> Vector2 {
-    Vector2::new(
+pub fn vector_between(a: Vec2, b: Vec2) -> Vec2 {
+    Vec2::new(
         (a.x + b.x) / 2.0,
         (a.y + b.y) / 2.0
     )
 }
 
-pub fn from_angle_to_vec2(angle: f32) -> Vector2 {
-    Vector2::new(
+pub fn from_angle_to_vec2(angle: f32) -> Vec2 {
+    Vec2::new(
         angle.cos(),
         angle.sin(),
     )
@@ -0,0 +1,161 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use serde::Serialize;
+
+/**
+One line of the training log, fitness per episode.
+ */
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GenerationMetrics {
+    pub generation: usize, // from 1
+    pub best: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub std: f64,
+    pub holdout: f64, // mean hold-out score of the champion so far
+    pub episodes: usize, // played by the training fitness, to compare how many each optimiser needs
+    pub diversity: f64, // mean distance of the genomes to their centroid
+    pub gene_mean: Vec<f64>,
+    pub gene_spread: Vec<f64>, // standard deviation of each gene
+    pub seconds: f64, // wall time of the generation
+}
+
+impl GenerationMetrics {
+    /**
+    `genes` holds the genes of every brain of the generation, in the same order as `fitness`.
+     */
+    pub fn new(generation: usize, fitness: &[f64], genes: &[Vec<f64>], holdout: f64, episodes: usize, seconds: f64) -> GenerationMetrics {
+        let mut sorted = fitness.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let num_genes = genes.first().map_or(0, |brain| brain.len());
+        let gene_mean: Vec<f64> = (0..num_genes)
+            .map(|i| mean(genes.iter().map(|brain| brain[i])))
+            .collect();
+        let gene_spread: Vec<f64> = (0..num_genes)
+            .map(|i| std(genes.iter().map(|brain| brain[i])))
+            .collect();
+        let diversity = mean(genes.iter().map(|brain| {
+            brain.iter().zip(&gene_mean)
+                .map(|(gene, centroid)| (gene - centroid).powi(2))
+                .sum::<f64>()
+                .sqrt()
+        }));
+
+        GenerationMetrics {
+            generation,
+            best: sorted.last().copied().unwrap_or(0.0),
+            mean: mean(fitness.iter().copied()),
+            median: median(&sorted),
+            std: std(fitness.iter().copied()),
+            holdout,
+            episodes,
+            diversity,
+            gene_mean,
+            gene_spread,
+            seconds,
+        }
+    }
+
+    fn csv_header(&self) -> String {
+        let mut columns: Vec<String> = ["generation", "best", "mean", "median", "std", "holdout", "episodes", "diversity"].iter()
+            .map(|column| column.to_string())
+            .collect();
+        columns.extend((0..self.gene_mean.len()).map(|i| format!("gene_mean_{}", i)));
+        columns.extend((0..self.gene_spread.len()).map(|i| format!("gene_spread_{}", i)));
+        columns.push("seconds".to_string());
+        columns.join(",")
+    }
+
+    fn csv_row(&self) -> String {
+        let mut values = vec![
+            self.generation.to_string(),
+            self.best.to_string(),
+            self.mean.to_string(),
+            self.median.to_string(),
+            self.std.to_string(),
+            self.holdout.to_string(),
+            self.episodes.to_string(),
+            self.diversity.to_string(),
+        ];
+        values.extend(self.gene_mean.iter().map(f64::to_string));
+        values.extend(self.gene_spread.iter().map(f64::to_string));
+        values.push(self.seconds.to_string());
+        values.join(",")
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, n) = values.fold((0.0, 0), |(sum, n), value| (sum + value, n + 1));
+    if n == 0 { 0.0 } else { sum / n as f64 }
+}
+
+fn std(values: impl Iterator<Item = f64> + Clone) -> f64 {
+    let m = mean(values.clone());
+    mean(values.map(|value| (value - m).powi(2))).sqrt()
+}
+
+fn median(sorted: &[f64]) -> f64 {
+    match sorted.len() {
+        0 => 0.0,
+        n if n % 2 == 1 => sorted[n / 2],
+        n => (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0,
+    }
+}
+
+/**
+Appends one record per generation, as CSV if the file ends with `.csv`, JSON lines otherwise.
+ */
+pub struct MetricsLog {
+    file: File,
+    csv: bool,
+    needs_header: bool,
+}
+
+impl MetricsLog {
+    /**
+    A new run starts the file over, a resumed one (`append`) goes on with it.
+     */
+    pub fn open(path: &str, append: bool) -> io::Result<MetricsLog> {
+        let file = if append {
+            OpenOptions::new().create(true).append(true).open(path)?
+        } else {
+            File::create(path)?
+        };
+        let needs_header = file.metadata()?.len() == 0;
+
+        Ok(MetricsLog {
+            file,
+            csv: path.ends_with(".csv"),
+            needs_header,
+        })
+    }
+
+    pub fn write(&mut self, metrics: &GenerationMetrics) -> io::Result<()> {
+        if self.csv {
+            if self.needs_header {
+                writeln!(self.file, "{}", metrics.csv_header())?;
+                self.needs_header = false;
+            }
+            writeln!(self.file, "{}", metrics.csv_row())
+        } else {
+            writeln!(self.file, "{}", serde_json::to_string(metrics)?)
+        }
+    }
+}
+
+#[test]
+fn metrics_of_a_generation() {
+    let genes = vec![vec![0.0, 1.0], vec![2.0, 1.0], vec![1.0, 1.0], vec![1.0, 1.0]];
+    let metrics = GenerationMetrics::new(3, &[4.0, 1.0, 3.0, 2.0], &genes, 2.5, 20, 0.1);
+
+    assert_eq!(metrics.best, 4.0);
+    assert_eq!(metrics.mean, 2.5);
+    assert_eq!(metrics.median, 2.5);
+    assert!((metrics.std - 1.25f64.sqrt()).abs() < 1e-12);
+    assert_eq!(metrics.gene_mean, vec![1.0, 1.0]);
+    assert!((metrics.gene_spread[0] - 0.5f64.sqrt()).abs() < 1e-12);
+    assert_eq!(metrics.gene_spread[1], 0.0);
+    assert_eq!(metrics.diversity, 0.5);
+    assert_eq!(metrics.csv_header().split(',').count(), metrics.csv_row().split(',').count());
+}
\ No newline at end of file
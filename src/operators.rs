@@ -0,0 +1,621 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use genevo::algorithm::EvaluatedPopulation;
+use genevo::genetic::{Children, Fitness, Genotype, Offspring, Parents};
+use genevo::operator::prelude::*;
+use rand::Rng;
+use rand_distr::StandardNormal;
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::fitness::{FitnessValue, RolloutFitness};
+use crate::train::{Genome, TrainConfig};
+
+/**
+How the parents of the next generation are picked.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SelectionKind {
+    Best, // the fittest, paired in order
+    Tournament,
+}
+
+impl FromStr for SelectionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "best" => Ok(SelectionKind::Best),
+            "tournament" => Ok(SelectionKind::Tournament),
+            _ => Err(format!("unknown selection: {}", s)),
+        }
+    }
+}
+
+/**
+How two parents are mixed into children.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CrossoverKind {
+    Uniform, // each gene from either parent
+    Blend, // each gene drawn around both parents (BLX-α)
+}
+
+impl FromStr for CrossoverKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(CrossoverKind::Uniform),
+            "blend" => Ok(CrossoverKind::Blend),
+            _ => Err(format!("unknown crossover: {}", s)),
+        }
+    }
+}
+
+/**
+How a child is changed after crossover.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MutationKind {
+    Uniform, // each gene nudged by up to its sigma, with probability `mutation_rate`
+    Gaussian, // each gene nudged by a normal step of its sigma, with probability `mutation_rate`
+    SelfAdaptive, // every gene nudged by a normal step of its sigma times a step size tuned by the 1/5th success rule
+}
+
+impl FromStr for MutationKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(MutationKind::Uniform),
+            "gaussian" => Ok(MutationKind::Gaussian),
+            "self-adaptive" => Ok(MutationKind::SelfAdaptive),
+            _ => Err(format!("unknown mutation: {}", s)),
+        }
+    }
+}
+
+/**
+What happens to a gene mutated out of its range.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BoundsKind {
+    Clamp, // it sticks to the bound
+    Reflect, // it bounces back by as much as it overshot
+}
+
+impl FromStr for BoundsKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(BoundsKind::Clamp),
+            "reflect" => Ok(BoundsKind::Reflect),
+            _ => Err(format!("unknown bounds: {}", s)),
+        }
+    }
+}
+
+/**
+Valid range of a gene, where random brains start and how far a mutation moves it.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeneSpec {
+    pub name: &'static str,
+    pub min: f64,
+    pub max: f64,
+    pub initial: (f64, f64),
+    pub sigma: f64, // reach of a uniform mutation, standard deviation of a normal one
+}
+
+impl GeneSpec {
+    /**
+    Where the gene is searched: its range, or where random brains start when it has none.
+     */
+    fn search_range(&self) -> (f64, f64) {
+        if (self.max - self.min).is_finite() {
+            (self.min, self.max)
+        } else {
+            self.initial
+        }
+    }
+
+    /**
+    The gene mapped to 0..1 over its search range.
+     */
+    pub fn scale(&self, gene: f64) -> f64 {
+        let (low, high) = self.search_range();
+        (gene - low) / (high - low)
+    }
+
+    pub fn unscale(&self, x: f64) -> f64 {
+        let (low, high) = self.search_range();
+        low + x * (high - low)
+    }
+}
+
+/**
+Bounds of the step size of a self-adaptive mutation, so it can neither freeze nor explode.
+ */
+const STEP_SIZE_RANGE: (f64, f64) = (0.01, 10.0);
+
+pub(crate) fn default_step_size() -> f64 {
+    1.0
+}
+
+/**
+Step size of a self-adaptive mutation, tuned by Rechenberg's 1/5th success rule:
+when more than a fifth of the children of a generation beat their fitter parent the search
+is too timid and the step grows, when fewer do it overshoots and the step shrinks.
+The breeder notes the parents of every child and the mutator follows it through its mutation,
+so the reinserter can judge it against them on the episodes of the generation that bred it.
+ */
+#[derive(Debug, Clone)]
+pub struct StepSize {
+    size: Arc<RwLock<f64>>,
+    parents: Arc<Mutex<Lineage>>, // of the children not judged yet
+}
+
+/**
+Both parents of the children, by the genes of the child.
+ */
+type Lineage = HashMap<Vec<u64>, Vec<[Vec<u64>; 2]>>;
+
+/**
+The bits of `genes`, to find an individual by.
+ */
+fn genes_key(genes: &[f64]) -> Vec<u64> {
+    genes.iter().map(|gene| gene.to_bits()).collect()
+}
+
+impl StepSize {
+    pub fn new(step_size: f64) -> StepSize {
+        StepSize {
+            size: Arc::new(RwLock::new(step_size)),
+            parents: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn get(&self) -> f64 {
+        *self.size.read().unwrap()
+    }
+
+    fn note_parents(&self, child: &[f64], parent1: &[f64], parent2: &[f64]) {
+        self.parents.lock().unwrap()
+            .entry(genes_key(child))
+            .or_default()
+            .push([genes_key(parent1), genes_key(parent2)]);
+    }
+
+    fn note_mutation(&self, before: &[f64], after: &[f64]) {
+        let mut parents = self.parents.lock().unwrap();
+        let noted = parents.get_mut(&genes_key(before)).and_then(|noted| noted.pop());
+        if let Some(noted) = noted {
+            parents.entry(genes_key(after)).or_default().push(noted);
+        }
+    }
+
+    /**
+    Judges the `offspring` against the fitter of their parents among `individuals`,
+    both on the episodes the child plays in the current generation, then applies the rule.
+    The parents keep their `fitness_values` when every brain plays the same episodes.
+     */
+    pub fn adapt<G: Genome>(&self, offspring: &[G], individuals: &[G], fitness_values: &[f64], fitness: &RolloutFitness) {
+        let mut parents = std::mem::take(&mut *self.parents.lock().unwrap());
+        let index: HashMap<Vec<u64>, usize> = individuals.iter()
+            .enumerate()
+            .map(|(i, brain)| (genes_key(&brain.gene_values()), i))
+            .collect();
+        let judged: Vec<(&G, usize)> = offspring.iter()
+            .filter_map(|child| {
+                let [parent1, parent2] = parents.get_mut(&genes_key(&child.gene_values()))?.pop()?;
+                let fitter = [index.get(&parent1)?, index.get(&parent2)?].into_iter()
+                    .max_by(|&&a, &&b| fitness_values[a].total_cmp(&fitness_values[b]))?;
+                Some((child, *fitter))
+            })
+            .collect();
+
+        let outcomes: Vec<bool> = judged.par_iter()
+            .map(|&(child, parent)| {
+                let episodes = fitness.episodes_for(child);
+                let parent_fitness = if fitness.shares_episodes() {
+                    fitness_values[parent]
+                } else {
+                    fitness.score_on(&individuals[parent], &episodes).mean
+                };
+                fitness.score_on(child, &episodes).mean > parent_fitness
+            })
+            .collect();
+        self.apply(&outcomes);
+    }
+
+    /**
+    The rule, for whether each child beat its fitter parent.
+     */
+    fn apply(&self, outcomes: &[bool]) {
+        if outcomes.is_empty() {
+            return;
+        }
+
+        let success_rate = outcomes.iter().filter(|&&success| success).count() as f64 / outcomes.len() as f64;
+        let factor = if success_rate > 0.2 {
+            1.22
+        } else if success_rate < 0.2 {
+            0.82
+        } else {
+            1.0
+        };
+        let mut step_size = self.size.write().unwrap();
+        *step_size = (*step_size * factor).clamp(STEP_SIZE_RANGE.0, STEP_SIZE_RANGE.1);
+    }
+}
+
+/**
+How the children and the previous generation make up the next one.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReinsertionKind {
+    Elitist, // the best of the previous generation survive
+    Uniform, // random ones survive
+}
+
+impl FromStr for ReinsertionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "elitist" => Ok(ReinsertionKind::Elitist),
+            "uniform" => Ok(ReinsertionKind::Uniform),
+            _ => Err(format!("unknown reinsertion: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Selector {
+    Best(MaximizeSelector),
+    Tournament(TournamentSelector),
+}
+
+impl Selector {
+    pub fn new(config: &TrainConfig) -> Selector {
+        match config.selection {
+            SelectionKind::Best => Selector::Best(MaximizeSelector::new(config.selection_ratio, 2)),
+            SelectionKind::Tournament => Selector::Tournament(TournamentSelector::new(
+                config.selection_ratio,
+                2,
+                config.tournament_size,
+                1.0, // the fittest of a tournament always wins
+                false
+            )),
+        }
+    }
+}
+
+impl GeneticOperator for Selector {
+    fn name() -> String {
+        "Selector".to_string()
+    }
+}
+
+impl<G: Genotype, F: Fitness> SelectionOp<G, F> for Selector {
+    fn select_from<R>(&self, population: &EvaluatedPopulation<G, F>, rng: &mut R) -> Vec<Parents<G>>
+    where
+        R: Rng + Sized,
+    {
+        match self {
+            Selector::Best(selector) => selector.select_from(population, rng),
+            Selector::Tournament(selector) => selector.select_from(population, rng),
+        }
+    }
+}
+
+/**
+Crossover through the `Genome` of the brain, every parent gets one child.
+ */
+#[derive(Clone, Debug)]
+pub struct Breeder {
+    kind: CrossoverKind,
+    blend_alpha: f64,
+    bounds: BoundsKind, // for blended genes past a bound
+    step_size: Option<StepSize>, // notes the parents of every child, with a self-adaptive mutation
+}
+
+impl Breeder {
+    pub fn new(config: &TrainConfig, step_size: StepSize) -> Breeder {
+        Breeder {
+            kind: config.crossover,
+            blend_alpha: config.blend_alpha,
+            bounds: config.bounds,
+            step_size: (config.mutation == MutationKind::SelfAdaptive).then_some(step_size),
+        }
+    }
+}
+
+impl GeneticOperator for Breeder {
+    fn name() -> String {
+        "Breeder".to_string()
+    }
+}
+
+impl<G: Genome> CrossoverOp<G> for Breeder {
+    fn crossover<R>(&self, parents: Parents<G>, rng: &mut R) -> Children<G>
+    where
+        R: Rng + Sized,
+    {
+        (0..parents.len())
+            .map(|i| {
+                let parent1 = &parents[i];
+                let parent2 = &parents[(i + 1) % parents.len()];
+                let child = match self.kind {
+                    CrossoverKind::Uniform => parent1.crossover(parent2, rng),
+                    CrossoverKind::Blend => parent1.blend(parent2, self.blend_alpha, self.bounds, rng),
+                };
+                if let Some(step_size) = &self.step_size {
+                    step_size.note_parents(&child.gene_values(), &parent1.gene_values(), &parent2.gene_values());
+                }
+                child
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Mutator {
+    kind: MutationKind,
+    rate: f64,
+    scale: f64, // of every sigma
+    bounds: BoundsKind,
+    step_size: StepSize, // only tuned by the genetic algorithm of `train`, constant elsewhere
+}
+
+impl Mutator {
+    pub fn new(config: &TrainConfig) -> Mutator {
+        Mutator::with_step_size(config, StepSize::new(default_step_size()))
+    }
+
+    pub fn with_step_size(config: &TrainConfig, step_size: StepSize) -> Mutator {
+        Mutator {
+            kind: config.mutation,
+            rate: config.mutation_rate,
+            scale: config.mutation_scale,
+            bounds: config.bounds,
+            step_size,
+        }
+    }
+
+    pub fn mutate_genes<R: Rng>(&self, genes: &mut [f64], specs: &[GeneSpec], rng: &mut R) {
+        match self.kind {
+            MutationKind::Uniform => {
+                for (gene, spec) in genes.iter_mut().zip(specs) {
+                    if rng.gen_bool(self.rate) {
+                        let reach = spec.sigma * self.scale;
+                        *gene = self.bound(*gene + rng.gen_range(-reach..reach), spec);
+                    }
+                }
+            }
+            MutationKind::Gaussian => {
+                for (gene, spec) in genes.iter_mut().zip(specs) {
+                    if rng.gen_bool(self.rate) {
+                        let step: f64 = rng.sample(StandardNormal);
+                        *gene = self.bound(*gene + step * spec.sigma * self.scale, spec);
+                    }
+                }
+            }
+            MutationKind::SelfAdaptive => {
+                let step_size = self.step_size.get();
+                for (gene, spec) in genes.iter_mut().zip(specs) {
+                    let step: f64 = rng.sample(StandardNormal);
+                    *gene = self.bound(*gene + step * spec.sigma * self.scale * step_size, spec);
+                }
+            }
+        }
+    }
+
+    fn bound(&self, gene: f64, spec: &GeneSpec) -> f64 {
+        bound(gene, spec, self.bounds)
+    }
+}
+
+/**
+`gene` brought back into the range of `spec` if it left it.
+ */
+pub(crate) fn bound(gene: f64, spec: &GeneSpec, bounds: BoundsKind) -> f64 {
+    match bounds {
+        BoundsKind::Clamp => gene.clamp(spec.min, spec.max),
+        BoundsKind::Reflect => reflect(gene, spec.min, spec.max),
+    }
+}
+
+impl GeneticOperator for Mutator {
+    fn name() -> String {
+        "Mutator".to_string()
+    }
+}
+
+impl<G: Genome> MutationOp<G> for Mutator {
+    fn mutate<R>(&self, mut genome: G, rng: &mut R) -> G
+    where
+        R: Rng + Sized,
+    {
+        let before = genome.gene_values();
+        genome.mutate(self, rng);
+        self.step_size.note_mutation(&before, &genome.gene_values());
+        genome
+    }
+}
+
+/**
+`elite_fraction` of the previous generation is kept, children fill the rest.
+With a self-adaptive mutation the children are judged first, for its step size.
+ */
+#[derive(Clone, Debug)]
+pub struct Reinserter<G: Genome> {
+    kind: Reinsertion<G>,
+    fitness: RolloutFitness,
+    step_size: Option<StepSize>,
+}
+
+#[derive(Clone, Debug)]
+enum Reinsertion<G: Genome> {
+    Elitist(Box<ElitistReinserter<G, FitnessValue, RolloutFitness>>),
+    Uniform(UniformReinserter),
+}
+
+impl<G: Genome> Reinserter<G> {
+    pub fn new(config: &TrainConfig, fitness: RolloutFitness, step_size: StepSize) -> Reinserter<G> {
+        let replace_ratio = 1.0 - config.elite_fraction;
+        let kind = match config.reinsertion {
+            ReinsertionKind::Elitist => Reinsertion::Elitist(Box::new(ElitistReinserter::new(fitness.clone(), false, replace_ratio))),
+            ReinsertionKind::Uniform => Reinsertion::Uniform(UniformReinserter::new(replace_ratio)),
+        };
+        Reinserter {
+            kind,
+            fitness,
+            step_size: (config.mutation == MutationKind::SelfAdaptive).then_some(step_size),
+        }
+    }
+}
+
+impl<G: Genome> GeneticOperator for Reinserter<G> {
+    fn name() -> String {
+        "Reinserter".to_string()
+    }
+}
+
+impl<G: Genome> ReinsertionOp<G, FitnessValue> for Reinserter<G> {
+    fn combine<R>(&self, offspring: &mut Offspring<G>, population: &EvaluatedPopulation<G, FitnessValue>, rng: &mut R) -> Vec<G>
+    where
+        R: Rng + Sized,
+    {
+        // the layouts are still the ones the parents were scored on
+        if let Some(step_size) = &self.step_size {
+            let fitness_values: Vec<f64> = population.fitness_values().iter().map(|value| value.0).collect();
+            step_size.adapt(offspring, population.individuals(), &fitness_values, &self.fitness);
+        }
+
+        match &self.kind {
+            Reinsertion::Elitist(reinserter) => reinserter.combine(offspring, population, rng),
+            Reinsertion::Uniform(reinserter) => reinserter.combine(offspring, population, rng),
+        }
+    }
+}
+
+/**
+A gene drawn uniformly around both parent genes, reaching `alpha` times their distance past each of them.
+ */
+pub(crate) fn blend_gene<R: Rng>(gene1: f64, gene2: f64, alpha: f64, rng: &mut R) -> f64 {
+    let low = gene1.min(gene2);
+    let high = gene1.max(gene2);
+    let reach = (high - low) * alpha;
+    rng.gen_range(low - reach..=high + reach)
+}
+
+/**
+`value` folded back into `min..=max`, as if the bounds were mirrors.
+ */
+pub(crate) fn reflect(value: f64, min: f64, max: f64) -> f64 {
+    let width = max - min;
+    if !width.is_finite() || width <= 0.0 {
+        return value.clamp(min, max);
+    }
+
+    let folded = (value - min).rem_euclid(2.0 * width);
+    min + if folded > width { 2.0 * width - folded } else { folded }
+}
+
+#[test]
+fn blend_gene_stays_within_reach() {
+    let mut rng = crate::math::seeded_rng(7);
+
+    for _ in 0..1000 {
+        let gene1 = rng.gen_range(-5.0..5.0);
+        let gene2 = rng.gen_range(-5.0..5.0);
+        let gene = blend_gene(gene1, gene2, 0.5, &mut rng);
+        let reach = (gene1 - gene2).abs() * 0.5;
+
+        assert!(gene >= gene1.min(gene2) - reach && gene <= gene1.max(gene2) + reach);
+    }
+
+    assert_eq!(blend_gene(1.5, 1.5, 0.5, &mut rng), 1.5);
+}
+
+#[test]
+fn reflected_genes_stay_in_range() {
+    assert!((reflect(1.2, 0.0, 1.0) - 0.8).abs() < 1e-12);
+    assert!((reflect(-0.3, 0.0, 1.0) - 0.3).abs() < 1e-12);
+    assert!((reflect(3.5, 1.0, 3.0) - 2.5).abs() < 1e-12);
+    assert!((reflect(5.5, 1.0, 3.0) - 1.5).abs() < 1e-12); // past both bounds
+    assert_eq!(reflect(0.4, 0.0, 1.0), 0.4);
+    assert_eq!(reflect(-7.0, f64::NEG_INFINITY, f64::INFINITY), -7.0);
+
+    let mutator = Mutator {
+        kind: MutationKind::SelfAdaptive,
+        rate: 1.0,
+        scale: 5.0,
+        bounds: BoundsKind::Reflect,
+        step_size: StepSize::new(2.0),
+    };
+    let specs = crate::brain::weighted::GENES;
+    let mut rng = crate::math::seeded_rng(11);
+    let mut genes: Vec<f64> = specs.iter().map(|spec| (spec.initial.0 + spec.initial.1) / 2.0).collect();
+    for _ in 0..1000 {
+        mutator.mutate_genes(&mut genes, &specs, &mut rng);
+        for (gene, spec) in genes.iter().zip(&specs) {
+            assert!((spec.min..=spec.max).contains(gene), "{} is {}", spec.name, gene);
+        }
+    }
+
+    // parents at opposite bounds, so blended children reach past them
+    let parent1 = crate::brain::weighted::WormBrain::from_genes(&specs.map(|spec| spec.min));
+    let parent2 = crate::brain::weighted::WormBrain::from_genes(&specs.map(|spec| spec.max));
+    for _ in 0..100 {
+        let child = parent1.blend(&parent2, 0.5, BoundsKind::Reflect, &mut rng);
+        for (gene, spec) in child.gene_values().iter().zip(&specs) {
+            assert!((spec.min..=spec.max).contains(gene), "{} is {}", spec.name, gene);
+        }
+    }
+}
+
+#[test]
+fn step_size_follows_the_one_fifth_rule() {
+    let step_size = StepSize::new(1.0);
+
+    step_size.apply(&[true, false, false]); // one success in three
+    assert_eq!(step_size.get(), 1.22);
+    step_size.apply(&[false, false, false, false, false, false]);
+    assert!((step_size.get() - 1.22 * 0.82).abs() < 1e-12);
+    step_size.apply(&[]); // no children, no change
+    assert!((step_size.get() - 1.22 * 0.82).abs() < 1e-12);
+}
+
+#[test]
+fn children_are_judged_on_the_episodes_of_their_generation() {
+    use crate::brain::weighted::WormBrain;
+
+    let config = TrainConfig {
+        iterations: 100,
+        episodes: 2,
+        common_layouts: false,
+        mutation: MutationKind::SelfAdaptive,
+        ..TrainConfig::default()
+    };
+    let fitness = RolloutFitness::new(&config);
+    fitness.next_generation(&mut crate::math::seeded_rng(3));
+    let step_size = StepSize::new(1.0);
+
+    // a child no different from its parent, whose fitness is from the layouts of an older generation
+    let parent = WormBrain::from_genes(&[0.5, 1.0, 0.5, 1.0, 1.0, 0.0, 1.0, 0.0]);
+    let genes = parent.gene_values();
+    step_size.note_parents(&genes, &genes, &genes);
+    let generation = std::slice::from_ref(&parent);
+    step_size.adapt(generation, generation, &[f64::MIN], &fitness);
+
+    assert_eq!(step_size.get(), 0.82); // it ties on the same episodes, which isn't a success
+}
\ No newline at end of file
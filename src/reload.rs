@@ -0,0 +1,58 @@
+use std::fs;
+use std::time::SystemTime;
+use crate::train::{load_model, ModelFile};
+
+pub enum Reload {
+    Unchanged,
+    Loaded(Box<ModelFile>), // boxed, a model is much larger than the other variants
+    Rejected(String),
+}
+
+/**
+Watches the model file and loads it again whenever its mtime changes,
+so a model retrained in another terminal shows up live.
+A file that fails to load is tried again at every poll until it loads, as it may still be written,
+but only reported once.
+ */
+pub struct ModelWatcher {
+    pub path: String,
+    last_seen: Option<Option<SystemTime>>, // None before the first poll, Some(None) while the file is missing
+    rejected: Option<SystemTime>, // mtime of the version that failed to load
+}
+
+impl ModelWatcher {
+    pub fn new(path: &str) -> ModelWatcher {
+        ModelWatcher {
+            path: path.to_string(),
+            last_seen: None,
+            rejected: None,
+        }
+    }
+
+    pub fn poll(&mut self) -> Reload {
+        let modified = fs::metadata(&self.path).and_then(|meta| meta.modified());
+        let current = modified.as_ref().ok().copied();
+
+        if self.last_seen == Some(current) {
+            return Reload::Unchanged;
+        }
+
+        if let Err(e) = modified {
+            self.last_seen = Some(current);
+            return Reload::Rejected(format!("{}: {}", self.path, e));
+        }
+
+        match load_model(&self.path) {
+            Ok(model) => {
+                self.last_seen = Some(current);
+                self.rejected = None;
+                Reload::Loaded(Box::new(model))
+            }
+            Err(_) if self.rejected == current => Reload::Unchanged,
+            Err(e) => {
+                self.rejected = current;
+                Reload::Rejected(format!("{}: {}", self.path, e))
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,27 @@
+use raylib::drawing::{RaylibDrawHandle, RaylibMode2D};
+use raylib::math::Vector2;
+use crate::food::draw::draw_foods;
+use crate::math::Vec2;
+use crate::sim::World;
+use crate::worm::draw::draw_worms;
+
+pub fn to_vector2(v: Vec2) -> Vector2 {
+    Vector2::new(v.x, v.y)
+}
+
+pub fn from_vector2(v: Vector2) -> Vec2 {
+    Vec2::new(v.x, v.y)
+}
+
+/**
+Draws the current state of the world, `percentage_animation` (from 0 to 1)
+interpolates between the previous and the current tick.
+ */
+pub fn draw_world(
+    d2d: &mut RaylibMode2D<RaylibDrawHandle>,
+    world: &World,
+    percentage_animation: f32
+) {
+    draw_foods(d2d, &world.foods, &world.rules, percentage_animation);
+    draw_worms(d2d, &world.worms, percentage_animation);
+}
\ No newline at end of file
@@ -0,0 +1,99 @@
+pub mod rules;
+pub mod steer;
+
+use crate::food::Food;
+use crate::food::generate::generate_food;
+use crate::math::{seeded_rng, SimRng};
+use crate::sim::rules::Rules;
+use crate::sim::steer::change_worms_direction;
+use crate::brain::SharedBrain;
+use crate::worm::Worm;
+use crate::worm::generate::generate_worms;
+use crate::worm::r#move::move_worms;
+use crate::worm::starve::{feed_worms, starve_worms};
+
+/**
+The whole simulation state, independent of any window.
+The viewer reads it to draw, training steps it without rendering.
+ */
+pub struct World {
+    pub rules: Rules,
+    pub worms: Vec<Worm>, // alive
+    pub dead: Vec<Worm>, // starved, kept for their stats
+    pub foods: Vec<Food>,
+    pub rng: SimRng, // every random decision after the spawn
+    pub tick: u64,
+}
+
+impl World {
+    pub fn new(rules: Rules, worms: Vec<Worm>, foods: Vec<Food>, rng: SimRng) -> World {
+        World {
+            rules,
+            worms,
+            dead: Vec::new(),
+            foods,
+            rng,
+            tick: 0,
+        }
+    }
+
+    pub fn random(rules: Rules, num_worms: i32, num_food: i32, seed: u64) -> World {
+        let mut rng = seeded_rng(seed);
+        let worms = generate_worms(num_worms, &rules, &mut rng);
+        let foods = generate_food(num_food, &rules, &mut rng);
+        World::new(rules, worms, foods, rng)
+    }
+
+    /**
+    Gives every worm a brain, cycling through `brains`
+    when there are more worms than brains.
+     */
+    pub fn assign_brains(&mut self, brains: &[SharedBrain]) {
+        if brains.is_empty() {
+            return;
+        }
+
+        for worm in self.worms.iter_mut() {
+            worm.brain = Some(brains[worm.id % brains.len()].clone());
+        }
+    }
+
+    pub fn find_worm(&self, id: usize) -> Option<&Worm> {
+        self.worms.iter()
+            .chain(self.dead.iter())
+            .find(|worm| worm.id == id)
+    }
+
+    /**
+    Advances the simulation by `dt` ticks.
+     */
+    pub fn step(&mut self, dt: f32) {
+        change_worms_direction(&mut self.worms, &self.foods, &self.rules, self.tick, &mut self.rng);
+        move_worms(&mut self.worms, &self.rules, dt);
+        starve_worms(&mut self.worms, &mut self.dead, &self.rules, dt);
+        feed_worms(&mut self.worms, &mut self.foods, &self.rules, dt);
+        self.tick += 1;
+    }
+}
+
+#[test]
+fn same_seed_same_trajectory() {
+    let brain: crate::brain::weighted::WormBrain = serde_json::from_str(
+        r#"{"food_attraction":0.5,"speed_factor":2.0,"life_threshold":0.3}"#
+    ).unwrap();
+
+    let run = |seed: u64| {
+        let mut world = World::random(Rules::default(), 20, 10, seed);
+        world.assign_brains(&[crate::brain::share(&brain)]);
+
+        let mut trajectory = Vec::new();
+        for _ in 0..300 {
+            world.step(1.0);
+            trajectory.extend(world.worms.iter().map(|worm| (worm.pos, worm.life)));
+        }
+        trajectory
+    };
+
+    assert_eq!(run(7), run(7));
+    assert_ne!(run(7), run(8));
+}
\ No newline at end of file
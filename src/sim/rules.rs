@@ -0,0 +1,34 @@
+use serde::{Serialize, Deserialize};
+
+/**
+The rules of the world, shared by the viewer and by training
+so a brain trained in one behaves the same in the other.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Rules {
+    pub width: f32, // arena size, worms can't leave it
+    pub height: f32,
+    pub eat_radius: f32, // how far a worm reaches, added to the food radius
+    pub food_radius_scale: f32, // food radius = scale * sqrt(amount)
+    pub food_amount: (f32, f32), // range of a freshly generated food
+    pub bite: f32, // max food eaten per tick from a single food
+    pub energy_per_food: f32, // life gained per unit of food eaten
+    pub drain: f32, // life lost per tick per unit of speed
+    pub step_scale: f32, // distance covered per tick per unit of speed
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            width: 800.0,
+            height: 800.0,
+            eat_radius: 10.0,
+            food_radius_scale: 2.0,
+            food_amount: (20.0, 100.0),
+            bite: 1.0,
+            energy_per_food: 0.02,
+            drain: 0.001,
+            step_scale: 10.0,
+        }
+    }
+}
\ No newline at end of file
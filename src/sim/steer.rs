@@ -0,0 +1,35 @@
+use crate::brain::Observation;
+use crate::food::Food;
+use crate::math::{from_angle_to_vec2, SimRng, Vec2};
+use crate::sim::rules::Rules;
+use crate::worm::Worm;
+
+pub fn change_worms_direction(worms: &mut Vec<Worm>, foods: &Vec<Food>, rules: &Rules, tick: u64, rng: &mut SimRng) {
+    let positions: Vec<Vec2> = worms.iter().map(|worm| worm.pos).collect();
+
+    // Update each worm's direction using its own brain
+    for worm in worms.iter_mut() {
+        let Some(brain) = &worm.brain else {
+            continue;
+        };
+
+        let action = brain.decide(
+            &Observation {
+                tick,
+                pos: worm.pos,
+                heading: worm.dir,
+                life: worm.life,
+                speed: worm.speed,
+                foods,
+                worms: &positions,
+                rules,
+            },
+            rng,
+        );
+
+        // Store the calculated direction (in radians) in the worm
+        worm.dir = from_angle_to_vec2(action.heading);
+        worm.speed = action.speed;
+        worm.eating = action.eat;
+    }
+}
\ No newline at end of file
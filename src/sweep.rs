@@ -0,0 +1,350 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Write};
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::cli::validate;
+use crate::fitness::{sample_episodes, Episode};
+use crate::math::seeded_rng;
+use crate::train::TrainConfig;
+
+/**
+A sweep, as read from its spec file:
+
+```json
+{
+  "search": "grid",
+  "seeds": [1, 2, 3],
+  "base": {"generations": 30, "rules": {"drain": 0.001}},
+  "parameters": {
+    "population_size": [20, 50],
+    "mutation_rate": {"min": 0.1, "max": 0.4, "steps": 4},
+    "rules.drain": [0.0005, 0.001, 0.002]
+  }
+}
+```
+
+Parameters are paths into the training settings. A random search draws `samples` configurations
+instead, picking from the lists and uniformly within the ranges. The brain each run ends with
+is scored on `evaluation_episodes` episodes of `evaluation_seed`, the same for every run.
+ */
+#[derive(Deserialize, Debug)]
+pub struct SweepSpec {
+    #[serde(default)]
+    pub search: SearchKind,
+    #[serde(default = "default_samples")]
+    pub samples: usize, // configurations of a random search
+    #[serde(default)]
+    pub sample_seed: u64, // of the random search itself
+    pub seeds: Vec<u64>, // every configuration trains once per seed
+    #[serde(default)]
+    pub base: Value, // settings shared by every configuration, over the defaults
+    pub parameters: BTreeMap<String, ParameterSpec>,
+    #[serde(default)]
+    pub evaluation_seed: u64,
+    #[serde(default = "default_evaluation_episodes")]
+    pub evaluation_episodes: usize,
+}
+
+fn default_samples() -> usize {
+    10
+}
+
+fn default_evaluation_episodes() -> usize {
+    100
+}
+
+impl SweepSpec {
+    /**
+    The episodes every run of a configuration is ranked on. They come from their own stream
+    of `evaluation_seed`, so they are never among the episodes a run trained or picked its brain on.
+     */
+    pub fn evaluation(&self, config: &TrainConfig) -> Vec<Episode> {
+        let mut rng = seeded_rng(self.evaluation_seed);
+        rng.set_stream(4);
+        sample_episodes(self.evaluation_episodes, &config.rules, config.num_food, &mut rng)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchKind {
+    #[default]
+    Grid,
+    Random,
+}
+
+/**
+The values a parameter takes: a list, or a range (with `steps` evenly spaced values in a grid).
+A range over an integer setting only gives integers.
+ */
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ParameterSpec {
+    Values(Vec<Value>),
+    Range { min: f64, max: f64, steps: Option<usize> },
+}
+
+impl ParameterSpec {
+    /**
+    Rejects a parameter no configuration could be made of, naming it.
+     */
+    fn check(&self, name: &str) -> Result<(), String> {
+        match self {
+            ParameterSpec::Values(values) if values.is_empty() => Err(format!("{}: no value to pick", name)),
+            ParameterSpec::Values(_) => Ok(()),
+            ParameterSpec::Range { min, max, .. } if !min.is_finite() || !max.is_finite() => {
+                Err(format!("{}: the range {}..{} isn't finite", name, min, max))
+            }
+            ParameterSpec::Range { min, max, .. } if min > max => {
+                Err(format!("{}: the range minimum {} is above its maximum {}", name, min, max))
+            }
+            ParameterSpec::Range { steps: Some(0), .. } => Err(format!("{}: a range needs at least one step", name)),
+            ParameterSpec::Range { .. } => Ok(()),
+        }
+    }
+}
+
+/**
+One configuration of the sweep, with the parameter values that make it.
+ */
+#[derive(Debug, Clone)]
+pub struct Configuration {
+    pub parameters: Vec<(String, Value)>,
+    pub config: TrainConfig,
+}
+
+impl Configuration {
+    pub fn describe(&self) -> String {
+        self.parameters.iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+pub fn load_spec(path: &str) -> Result<SweepSpec, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let spec: SweepSpec = serde_json::from_reader(file).map_err(|e| format!("Invalid sweep spec {}: {}", path, e))?;
+    if spec.seeds.is_empty() {
+        return Err("a sweep needs at least one seed".to_string());
+    }
+    if spec.evaluation_episodes == 0 {
+        return Err("a sweep needs at least one evaluation episode".to_string());
+    }
+    for (name, parameter) in &spec.parameters {
+        parameter.check(name)?;
+    }
+    Ok(spec)
+}
+
+/**
+Every configuration to train, seeds aside.
+ */
+pub fn configurations(spec: &SweepSpec) -> Result<Vec<Configuration>, String> {
+    let mut base = serde_json::to_value(TrainConfig::default()).map_err(|e| e.to_string())?;
+    merge(&mut base, &spec.base);
+
+    let assignments: Vec<Vec<(String, Value)>> = match spec.search {
+        SearchKind::Grid => {
+            let mut assignments = vec![Vec::new()];
+            for (name, parameter) in &spec.parameters {
+                let values = grid_values(name, parameter, &base)?;
+                assignments = assignments.into_iter()
+                    .flat_map(|assignment| values.iter().map(move |value| {
+                        let mut assignment = assignment.clone();
+                        assignment.push((name.clone(), value.clone()));
+                        assignment
+                    }))
+                    .collect();
+            }
+            assignments
+        }
+        SearchKind::Random => {
+            let mut rng = seeded_rng(spec.sample_seed);
+            (0..spec.samples)
+                .map(|_| spec.parameters.iter()
+                    .map(|(name, parameter)| Ok((name.clone(), random_value(name, parameter, &base, &mut rng)?)))
+                    .collect::<Result<Vec<(String, Value)>, String>>())
+                .collect::<Result<_, String>>()?
+        }
+    };
+
+    assignments.into_iter()
+        .map(|parameters| {
+            let mut settings = base.clone();
+            for (name, value) in &parameters {
+                *setting(&mut settings, name)? = value.clone();
+            }
+            let config: TrainConfig = serde_json::from_value(settings)
+                .map_err(|e| format!("invalid sweep configuration {:?}: {}", parameters, e))?;
+            validate(&config)?;
+            Ok(Configuration { parameters, config })
+        })
+        .collect()
+}
+
+/**
+Copies every value of `patch` into `target`, going down into the objects both have.
+ */
+fn merge(target: &mut Value, patch: &Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(key) {
+                    Some(current) => merge(current, value),
+                    None => {
+                        target.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+/**
+The setting at a dotted path like `rules.drain`.
+ */
+fn setting<'a>(settings: &'a mut Value, path: &str) -> Result<&'a mut Value, String> {
+    path.split('.')
+        .try_fold(settings, |node, key| node.get_mut(key))
+        .ok_or_else(|| format!("unknown setting: {}", path))
+}
+
+fn is_integer(name: &str, base: &Value) -> Result<bool, String> {
+    let mut base = base.clone();
+    Ok(setting(&mut base, name)?.is_u64())
+}
+
+fn number(value: f64, integer: bool) -> Value {
+    if integer {
+        Value::from(value.round() as u64)
+    } else {
+        Value::from(value)
+    }
+}
+
+fn grid_values(name: &str, parameter: &ParameterSpec, base: &Value) -> Result<Vec<Value>, String> {
+    match parameter {
+        ParameterSpec::Values(values) => Ok(values.clone()),
+        ParameterSpec::Range { min, max, steps } => {
+            let integer = is_integer(name, base)?;
+            match *steps {
+                None => Err(format!("{}: a grid needs the steps of the range", name)),
+                Some(0) => Err(format!("{}: a range needs at least one step", name)),
+                // one value can't span the range, it is the minimum
+                Some(1) => Ok(vec![number(*min, integer)]),
+                Some(steps) => Ok((0..steps)
+                    .map(|i| number(min + (max - min) * i as f64 / (steps - 1) as f64, integer))
+                    .collect()),
+            }
+        }
+    }
+}
+
+fn random_value<R: Rng>(name: &str, parameter: &ParameterSpec, base: &Value, rng: &mut R) -> Result<Value, String> {
+    match parameter {
+        ParameterSpec::Values(values) if values.is_empty() => Err(format!("{}: no value to pick", name)),
+        ParameterSpec::Values(values) => Ok(values[rng.gen_range(0..values.len())].clone()),
+        ParameterSpec::Range { min, max, .. } => Ok(number(rng.gen_range(*min..=*max), is_integer(name, base)?)),
+    }
+}
+
+/**
+Evaluation score of a configuration, for each seed.
+ */
+#[derive(Debug, Clone)]
+pub struct SweepResult {
+    pub configuration: Configuration,
+    pub scores: Vec<(u64, f64)>,
+}
+
+impl SweepResult {
+    pub fn mean(&self) -> f64 {
+        self.scores.iter().map(|&(_, score)| score).sum::<f64>() / self.scores.len().max(1) as f64
+    }
+
+    /**
+    Sample standard deviation over the seeds.
+     */
+    pub fn std(&self) -> f64 {
+        if self.scores.len() < 2 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        let squares: f64 = self.scores.iter().map(|&(_, score)| (score - mean).powi(2)).sum();
+        (squares / (self.scores.len() - 1) as f64).sqrt()
+    }
+}
+
+/**
+The results ranked by mean evaluation score, best first, as CSV.
+ */
+pub fn write_summary(path: &str, results: &[SweepResult]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    let names: Vec<&String> = results.first()
+        .map(|result| result.configuration.parameters.iter().map(|(name, _)| name).collect())
+        .unwrap_or_default();
+
+    let mut header = vec!["rank".to_string(), "mean_score".to_string(), "std_score".to_string()];
+    header.extend(names.iter().map(|name| name.to_string()));
+    header.push("seeds".to_string());
+    writeln!(file, "{}", header.join(","))?;
+
+    for (rank, result) in results.iter().enumerate() {
+        let mut row = vec![(rank + 1).to_string(), result.mean().to_string(), result.std().to_string()];
+        row.extend(result.configuration.parameters.iter().map(|(_, value)| value.to_string().replace(',', ";")));
+        row.push(result.scores.iter().map(|(seed, _)| seed.to_string()).collect::<Vec<String>>().join(" "));
+        writeln!(file, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn grid_covers_every_combination() {
+    use crate::operators::MutationKind;
+    use crate::train::Optimiser;
+
+    let spec: SweepSpec = serde_json::from_str(r#"{
+        "seeds": [1, 2],
+        "base": {"generations": 7, "optimiser": "cma-es", "mutation": "self-adaptive", "rules": {"drain": 0.002}},
+        "parameters": {
+            "population_size": {"min": 10, "max": 30, "steps": 3},
+            "rules.drain": [0.001, 0.003]
+        }
+    }"#).unwrap();
+
+    let grid = configurations(&spec).unwrap();
+
+    assert_eq!(grid.len(), 6);
+    assert!(grid.iter().all(|configuration| configuration.config.generations == 7));
+    assert_eq!(grid[0].config.optimiser, Optimiser::CmaEs); // spelled as on the command line
+    assert_eq!(grid[0].config.mutation, MutationKind::SelfAdaptive);
+    let sizes: Vec<usize> = grid.iter().map(|configuration| configuration.config.population_size).collect();
+    assert_eq!(sizes, vec![10, 10, 20, 20, 30, 30]);
+    assert_eq!(grid[1].config.rules.drain, 0.003);
+    assert_eq!(grid[1].describe(), "population_size=10, rules.drain=0.003");
+
+    let unknown: SweepSpec = serde_json::from_str(r#"{"seeds": [1], "parameters": {"rules.gravity": [1.0]}}"#).unwrap();
+    assert!(configurations(&unknown).is_err());
+}
+
+#[test]
+fn invalid_parameters_are_rejected() {
+    let check = |parameter: &str| serde_json::from_str::<ParameterSpec>(parameter).unwrap().check("mutation_rate");
+
+    assert!(check(r#"{"min": 0.1, "max": 0.4, "steps": 4}"#).is_ok());
+    assert!(check(r#"{"min": 0.4, "max": 0.4}"#).is_ok());
+    assert_eq!(
+        check(r#"{"min": 0.4, "max": 0.1}"#),
+        Err("mutation_rate: the range minimum 0.4 is above its maximum 0.1".to_string())
+    );
+    assert!(check(r#"{"min": 0.1, "max": 0.4, "steps": 0}"#).unwrap_err().starts_with("mutation_rate:"));
+    assert!(check("[]").unwrap_err().starts_with("mutation_rate:"));
+
+    let single = ParameterSpec::Range { min: 0.2, max: 0.4, steps: Some(1) };
+    let base = serde_json::to_value(TrainConfig::default()).unwrap();
+    assert_eq!(grid_values("mutation_rate", &single, &base), Ok(vec![Value::from(0.2)]));
+}
\ No newline at end of file
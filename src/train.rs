@@ -1,361 +1,1045 @@
-use genevo::{
-    operator::prelude::*,
-    population::*,
-    prelude::*,
-    types::fmt::Display,
-};
+use genevo::algorithm::Algorithm;
+use genevo::prelude::{genetic_algorithm, Genotype, Population, Prng, SeedableRng};
 use rand::Rng;
+use rayon::prelude::*;
 use serde::{Serialize, Deserialize};
-use std::fs::File;
-use std::io::{self, Write};
-use crate::math::rand_int;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+use std::time::Instant;
+use crate::brain::{share, Brain, SharedBrain};
+use crate::brain::neural::NeuralBrain;
+use crate::brain::weighted::WormBrain;
+use crate::checkpoint::{interrupted, Checkpoint};
+use crate::cma::CmaEs;
+use crate::food::Food;
+use crate::fitness::{describe_fitness, episode_scores, play, sample_episodes, Episode, FitnessMode, Rollout, RolloutFitness, Score, Term};
+use crate::hall_of_fame::{Champion, HallOfFame};
+use crate::math::{seeded_rng, SimRng, Vec2};
+use crate::metrics::{GenerationMetrics, MetricsLog};
+use crate::brain::weighted::GENES;
+use crate::operators::{blend_gene, bound, default_step_size, Breeder, BoundsKind, CrossoverKind, GeneSpec, MutationKind, Mutator, ReinsertionKind, Reinserter, SelectionKind, Selector, StepSize};
+use crate::sim::rules::Rules;
+use crate::sim::World;
+use crate::worm::Worm;
 
-#[derive(Debug, Clone)]
-pub struct Food {
-    pub(crate) position: (i32, i32),
-    pub(crate) value: i32,
+/**
+Version of the model files written now, older ones are migrated when loaded.
+ */
+pub const MODEL_VERSION: u64 = 3;
+
+/**
+Where a model comes from.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Metadata {
+    pub generation: usize, // the brain was found in this generation, or the population is this one, from 1
+    pub fitness: String, // what `fitness` measures
+    pub config: TrainConfig, // seed, hyperparameters and arena
 }
 
-#[derive(Debug, Clone)]
-struct Worm {
-    position: (f64, f64),
-    life: f64,
-    food_consumed: i32,
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WormModel<B = WormBrain> {
+    pub(crate) version: u64,
+    pub(crate) brain: B,
+    pub(crate) fitness: f64,
+    pub(crate) metadata: Option<Metadata>, // unknown for migrated files
+    pub(crate) hall_of_fame: Vec<Champion<B>>, // best distinct brains of the run, `brain` first
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct WormBrain {
-    food_attraction: f64,
-    pub(crate) speed_factor: f64,
-    pub(crate) life_threshold: f64,
+impl<B> WormModel<B> {
+    pub fn new(brain: B, fitness: f64, metadata: Option<Metadata>) -> WormModel<B> {
+        WormModel {
+            version: MODEL_VERSION,
+            brain,
+            fitness,
+            metadata,
+            hall_of_fame: Vec::new(),
+        }
+    }
+
+    /**
+    The hall of fame, or just the brain when there is none.
+     */
+    pub fn archive(&self) -> Vec<&B> {
+        if self.hall_of_fame.is_empty() {
+            vec![&self.brain]
+        } else {
+            self.hall_of_fame.iter().map(|entry| &entry.brain).collect()
+        }
+    }
 }
 
+/**
+A whole population, one brain per worm.
+ */
 #[derive(Serialize, Deserialize, Debug)]
-pub struct WormModel {
-    pub(crate) brain: WormBrain,
-    fitness: i32,
+pub struct PopulationModel<B = WormBrain> {
+    pub(crate) version: u64,
+    pub(crate) brains: Vec<B>,
+    pub(crate) metadata: Option<Metadata>,
 }
 
-fn load_model(filename: &str) -> io::Result<WormModel> {
-    let file = File::open(filename)?;
-    let model = serde_json::from_reader(file)?;
-    Ok(model)
+impl<B> PopulationModel<B> {
+    pub fn new(brains: Vec<B>, metadata: Option<Metadata>) -> PopulationModel<B> {
+        PopulationModel {
+            version: MODEL_VERSION,
+            brains,
+            metadata,
+        }
+    }
 }
 
-
-fn save_model(model: &WormModel, filename: &str) -> io::Result<()> {
-    let file = File::create(filename)?;
-    serde_json::to_writer(file, model)?;
-    Ok(())
+/**
+Any file the viewer can steer worms with.
+ */
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum ModelFile {
+    Single(WormModel),
+    Population(PopulationModel),
+    Neural(WormModel<NeuralBrain>),
+    NeuralPopulation(PopulationModel<NeuralBrain>),
 }
-impl WormBrain {
-    pub(crate) fn calculate_direction(&self, worm_pos: (f64, f64), foods: &[Food]) -> f64 {
-        if foods.is_empty() {
-            // When no food is available, move in a circular pattern
-            let current_angle = worm_pos.1.atan2(worm_pos.0);
-            return (current_angle + 0.1) % (2.0 * std::f64::consts::PI);
+
+impl ModelFile {
+    pub fn brains(&self) -> Vec<SharedBrain> {
+        match self {
+            ModelFile::Single(model) => vec![share(&model.brain)],
+            ModelFile::Population(population) => shared(&population.brains),
+            ModelFile::Neural(model) => vec![share(&model.brain)],
+            ModelFile::NeuralPopulation(population) => shared(&population.brains),
+        }
+    }
+
+    /**
+    The hall of fame of a single model, a mixed population to spawn.
+     */
+    pub fn archive_brains(&self) -> Vec<SharedBrain> {
+        match self {
+            ModelFile::Single(model) => model.archive().into_iter().map(share).collect(),
+            ModelFile::Neural(model) => model.archive().into_iter().map(share).collect(),
+            _ => self.brains(),
+        }
+    }
+
+    pub fn metadata(&self) -> Option<&Metadata> {
+        match self {
+            ModelFile::Single(model) => model.metadata.as_ref(),
+            ModelFile::Population(population) => population.metadata.as_ref(),
+            ModelFile::Neural(model) => model.metadata.as_ref(),
+            ModelFile::NeuralPopulation(population) => population.metadata.as_ref(),
+        }
+    }
+
+    /**
+    The brains the genetic algorithm can keep evolving, if any.
+     */
+    pub fn weighted_brains(&self) -> Vec<WormBrain> {
+        match self {
+            ModelFile::Single(model) => vec![model.brain.clone()],
+            ModelFile::Population(population) => population.brains.clone(),
+            _ => Vec::new(),
         }
+    }
 
-        // Find the closest valuable food
-        let mut weighted_direction = 0.0;
-        let mut total_weight = 0.0;
+    fn check(&self) -> Result<(), ModelError> {
+        match self {
+            ModelFile::Single(model) => check_each(&model.archive(), |brain| brain.check_genes()),
+            ModelFile::Population(population) => check_each(&population.brains, WormBrain::check_genes),
+            ModelFile::Neural(model) => check_each(&model.archive(), |brain| {
+                brain.check_shape().map_err(ModelError::Shape)
+            }),
+            ModelFile::NeuralPopulation(population) => check_each(&population.brains, |brain| {
+                brain.check_shape().map_err(ModelError::Shape)
+            }),
+        }
+    }
+}
 
-        for food in foods {
-            let dx = food.position.0 as f64 - worm_pos.0;
-            let dy = food.position.1 as f64 - worm_pos.1;
-            let distance = (dx * dx + dy * dy).sqrt();
+fn check_each<B>(brains: &[B], check: impl Fn(&B) -> Result<(), ModelError>) -> Result<(), ModelError> {
+    brains.iter()
+        .enumerate()
+        .try_for_each(|(i, brain)| check(brain).map_err(|e| ModelError::InBrain(i, Box::new(e))))
+}
 
-            // Weight based on distance and food value
-            let weight = (food.value as f64) / (distance + 1.0);
-            let direction = dy.atan2(dx);
+fn shared<B: Brain + Clone + Send + Sync + 'static>(brains: &[B]) -> Vec<SharedBrain> {
+    brains.iter().map(share).collect()
+}
 
-            weighted_direction += direction * weight;
-            total_weight += weight;
+/**
+Why a model file was refused.
+ */
+#[derive(Debug)]
+pub enum ModelError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    UnsupportedVersion(String),
+    GeneOutOfRange { gene: &'static str, value: f64, min: f64, max: f64 },
+    Shape(String), // of the neural network
+    InBrain(usize, Box<ModelError>), // of a population
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelError::Io(e) => write!(f, "{}", e),
+            ModelError::Json(e) => write!(f, "{}", e),
+            ModelError::UnsupportedVersion(version) => write!(
+                f, "unsupported model version {}, this build reads up to {}", version, MODEL_VERSION
+            ),
+            ModelError::GeneOutOfRange { gene, value, min, max } => write!(
+                f, "{} is {}, expected {} to {}", gene, value, min, max
+            ),
+            ModelError::Shape(e) => write!(f, "{}", e),
+            ModelError::InBrain(i, e) => write!(f, "brain {}: {}", i, e),
         }
+    }
+}
 
-        let base_angle = if total_weight == 0.0 {
-            // If weights sum to zero, use current direction
-            worm_pos.1.atan2(worm_pos.0)
-        } else {
-            weighted_direction / total_weight
-        };
+impl std::error::Error for ModelError {}
+
+impl From<io::Error> for ModelError {
+    fn from(e: io::Error) -> Self {
+        ModelError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ModelError {
+    fn from(e: serde_json::Error) -> Self {
+        ModelError::Json(e)
+    }
+}
+
+pub fn load_model(filename: &str) -> Result<ModelFile, ModelError> {
+    let file = File::open(filename)?;
+    parse_model(serde_json::from_reader(file)?)
+}
+
+/**
+Migrates, reads and validates a model file.
+ */
+pub fn parse_model(value: Value) -> Result<ModelFile, ModelError> {
+    let model: ModelFile = serde_json::from_value(migrate(value)?)?;
+    model.check()?;
+    Ok(model)
+}
 
-        // Add small fixed randomness instead of variable range
-        let random_offset = rand::thread_rng().gen::<f64>() * 0.2 * (1.0 - self.food_attraction);
+/**
+The hall of fame of a single-brain model file, to seed a new run with.
+ */
+pub fn load_archive<G: DeserializeOwned + Clone>(filename: &str) -> Result<Vec<G>, ModelError> {
+    let file = File::open(filename)?;
+    let value: Value = serde_json::from_reader(file)?;
+    parse_model(value.clone())?;
+    let model: WormModel<G> = serde_json::from_value(migrate(value)?)?;
+    Ok(model.archive().into_iter().cloned().collect())
+}
 
-        // Ensure the result stays within [0, 2π]
-        (base_angle + random_offset) % (2.0 * std::f64::consts::PI)
+/**
+Brings a model file written by an older version up to `MODEL_VERSION`.
+ */
+fn migrate(mut value: Value) -> Result<Value, ModelError> {
+    let version = match value.get("version") {
+        None => 1, // before versioning: a bare `{brain, fitness}` or `{brains}`
+        Some(version) => version.as_u64()
+            .ok_or_else(|| ModelError::UnsupportedVersion(version.to_string()))?,
+    };
+    if version == 0 || version > MODEL_VERSION {
+        return Err(ModelError::UnsupportedVersion(version.to_string()));
+    }
+
+    let Value::Object(fields) = &mut value else {
+        return Ok(value); // not a model, serde will say so
+    };
+    if version < 2 {
+        // versioned, with metadata that old files don't know
+        fields.insert("metadata".to_string(), Value::Null);
     }
+    if version < 3 && fields.contains_key("brain") {
+        // single brains keep a hall of fame, empty before
+        fields.insert("hall_of_fame".to_string(), Value::Array(Vec::new()));
+    }
+    fields.insert("version".to_string(), Value::from(MODEL_VERSION));
+
+    Ok(value)
 }
 
 #[test]
-fn train() {
-    let initial_foods: Vec<Food> = (0..10)
-        .map(|_| Food {
-            position: (rand_int(0, 100), rand_int(0, 100)),
-            value: rand_int(2, 10),
-        })
-        .collect();
+fn old_models_are_migrated() {
+    let v1 = serde_json::json!({"brain": {"food_attraction": 0.7, "speed_factor": 1.9, "life_threshold": 0.3}, "fitness": 8});
 
-    let population_size = 50;
-    let mut population: Vec<WormBrain> = (0..population_size)
-        .map(|_| WormBrain {
-            food_attraction: rand::thread_rng().gen_range(0.5..1.0),
-            speed_factor: rand::thread_rng().gen_range(1.0..3.0),
-            life_threshold: rand::thread_rng().gen_range(0.2..0.8),
-        })
-        .collect();
+    let ModelFile::Single(model) = parse_model(v1).unwrap() else {
+        panic!("a single weighted brain");
+    };
+    assert_eq!(model.version, MODEL_VERSION);
+    assert_eq!(model.fitness, 8.0);
+    assert!(model.metadata.is_none());
+    assert!(model.hall_of_fame.is_empty());
+    assert_eq!(model.brain.genes()[3..], [1.0, 1.0, 0.0, 1.0, 0.0]); // genes added since steer as before
+}
 
-    let generations = 50;
-    let mut best_brain = population[0].clone();
-    let mut best_fitness = 0;
+#[test]
+fn invalid_models_are_rejected() {
+    let too_fast = serde_json::json!({"brains": [
+        {"food_attraction": 0.7, "speed_factor": 1.9, "life_threshold": 0.3},
+        {"food_attraction": 0.7, "speed_factor": 50.0, "life_threshold": 0.3},
+    ]});
+    match parse_model(too_fast) {
+        Err(ModelError::InBrain(1, e)) => assert!(matches!(*e, ModelError::GeneOutOfRange { gene: "speed_factor", .. })),
+        other => panic!("expected an out-of-range gene, got {:?}", other),
+    }
 
-    for generation in 0..generations {
-        let mut generation_fitness = Vec::new();
+    let future = serde_json::json!({"version": MODEL_VERSION + 1, "brains": []});
+    assert!(matches!(parse_model(future), Err(ModelError::UnsupportedVersion(_))));
+}
 
-        for (i, brain) in population.iter().enumerate() {
-            let fitness = evaluate_brain(brain, &initial_foods);
-            generation_fitness.push((i, fitness));
-        }
+/**
+Writes `model` to a temporary file first and renames it over `filename` once it is complete,
+so a crash or a reader polling the file never sees half of it.
+ */
+pub fn save_model<T: Serialize>(model: &T, filename: &str) -> io::Result<()> {
+    let temporary = format!("{}.tmp", filename);
+    let mut writer = BufWriter::new(File::create(&temporary)?);
+    serde_json::to_writer(&mut writer, model)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    fs::rename(&temporary, filename)
+}
 
-        generation_fitness.sort_by_key(|&(_, fitness)| std::cmp::Reverse(fitness));
+/**
+A brain the genetic algorithm can evolve.
+ */
+pub trait Genome: Brain + Genotype + Clone + Send + Sync + Serialize + DeserializeOwned + 'static {
+    fn random<R: Rng>(rng: &mut R) -> Self;
+    fn crossover<R: Rng>(&self, other: &Self, rng: &mut R) -> Self;
+    fn blend<R: Rng>(&self, other: &Self, alpha: f64, bounds: BoundsKind, rng: &mut R) -> Self;
+    fn gene_values(&self) -> Vec<f64>;
+    fn set_gene_values(&mut self, genes: &[f64]);
+    fn gene_specs(&self) -> Vec<GeneSpec>; // in the order of `gene_values`
 
-        if generation_fitness[0].1 > best_fitness {
-            best_fitness = generation_fitness[0].1;
-            best_brain = population[generation_fitness[0].0].clone();
-            println!("New best fitness: {} in generation {}", best_fitness, generation);
-        }
+    /**
+    Life below which the worm counts as hungry, for the behaviour of MAP-Elites.
+     */
+    fn hunger_threshold(&self) -> f64 {
+        0.5
+    }
 
-        // Select top performers
-        let top_performers: Vec<&WormBrain> = generation_fitness.iter()
-            .take(population_size / 4)
-            .map(|&(i, _)| &population[i])
-            .collect();
+    fn mutate<R: Rng>(&mut self, mutator: &Mutator, rng: &mut R) {
+        let mut genes = self.gene_values();
+        mutator.mutate_genes(&mut genes, &self.gene_specs(), rng);
+        self.set_gene_values(&genes);
+    }
+}
+
+impl Genotype for WormBrain {
+    type Dna = f64;
+}
 
-        // Create new population
-        let mut new_population = Vec::new();
+impl Genome for WormBrain {
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        random_brain(rng)
+    }
 
-        // Keep the best performer
-        new_population.push(best_brain.clone());
+    fn crossover<R: Rng>(&self, other: &Self, rng: &mut R) -> Self {
+        crossover(self, other, rng)
+    }
 
-        while new_population.len() < population_size {
-            let parent1 = &top_performers[rand::thread_rng().gen_range(0..top_performers.len())];
-            let parent2 = &top_performers[rand::thread_rng().gen_range(0..top_performers.len())];
+    fn blend<R: Rng>(&self, other: &Self, alpha: f64, bounds: BoundsKind, rng: &mut R) -> Self {
+        blend(self, other, alpha, bounds, rng)
+    }
 
-            let mut child = crossover(parent1, parent2);
-            mutate(&mut child);
-            new_population.push(child);
-        }
+    fn gene_values(&self) -> Vec<f64> {
+        self.genes().to_vec()
+    }
 
-        population = new_population;
+    fn set_gene_values(&mut self, genes: &[f64]) {
+        self.set_genes(genes)
     }
 
-    let model = WormModel {
-        brain: best_brain,
-        fitness: best_fitness,
-    };
+    fn gene_specs(&self) -> Vec<GeneSpec> {
+        GENES.to_vec()
+    }
 
-    if let Err(e) = save_model(&model, "worm_model.json") {
-        eprintln!("Failed to save model: {}", e);
-    } else {
-        println!("Model saved successfully with fitness: {}", best_fitness);
+    fn hunger_threshold(&self) -> f64 {
+        self.life_threshold
     }
 }
 
-fn evaluate_brain(brain: &WormBrain, foods: &[Food]) -> i32 {
-    let mut worm = Worm {
-        position: (50.0, 50.0),
-        life: 1.0,
-        food_consumed: 0,
-    };
+/**
+What searches the genomes.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Optimiser {
+    #[serde(rename = "ga")]
+    Genetic, // genevo, with the operators of the config
+    CmaEs,
+}
 
-    let mut available_foods = foods.to_vec();
-    let iterations = 500;
+impl FromStr for Optimiser {
+    type Err = String;
 
-    for _ in 0..iterations {
-        if worm.life <= 0.0 {
-            break;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ga" => Ok(Optimiser::Genetic),
+            "cma-es" => Ok(Optimiser::CmaEs),
+            _ => Err(format!("unknown optimiser: {}", s)),
         }
-
-        let direction = brain.calculate_direction(worm.position, &available_foods);
-        let speed = brain.speed_factor;
-
-        // Update position with boundary checking
-        let new_x = worm.position.0 + direction.cos() * speed;
-        let new_y = worm.position.1 + direction.sin() * speed;
-        worm.position.0 = new_x.max(0.0).min(100.0);
-        worm.position.1 = new_y.max(0.0).min(100.0);
-
-        available_foods.retain(|food| {
-            let distance = ((worm.position.0 - food.position.0 as f64).powi(2)
-                + (worm.position.1 - food.position.1 as f64).powi(2)).sqrt();
-
-            if distance < 2.0 {
-                worm.food_consumed += food.value;
-                worm.life = (worm.life + 0.2).min(1.0);
-                false
-            } else {
-                true
-            }
-        });
-
-        worm.life -= 0.001 * speed;
     }
+}
 
-    worm.food_consumed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BrainKind {
+    Weighted,
+    Neural,
 }
 
-fn crossover(parent1: &WormBrain, parent2: &WormBrain) -> WormBrain {
-    let mut rng = rand::thread_rng();
-    WormBrain {
-        food_attraction: if rng.gen_bool(0.5) { parent1.food_attraction } else { parent2.food_attraction },
-        speed_factor: if rng.gen_bool(0.5) { parent1.speed_factor } else { parent2.speed_factor },
-        life_threshold: if rng.gen_bool(0.5) { parent1.life_threshold } else { parent2.life_threshold },
+impl FromStr for BrainKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "weighted" => Ok(BrainKind::Weighted),
+            "neural" => Ok(BrainKind::Neural),
+            _ => Err(format!("unknown brain: {}", s)),
+        }
     }
 }
+/**
+Everything a training run needs, filled from the `train` subcommand flags.
+Settings missing from an older file keep their default.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TrainConfig {
+    pub optimiser: Optimiser,
+    pub population_size: usize, // also the samples of a CMA-ES generation
+    pub generations: usize,
+    pub elite_fraction: f64, // best share of a generation kept into the next one
+    pub mutation_rate: f64, // probability of mutating each gene, but with a self-adaptive mutation
+    pub selection: SelectionKind,
+    pub selection_ratio: f64, // parent pairs picked per generation, as a share of the population
+    pub tournament_size: usize,
+    pub crossover: CrossoverKind,
+    pub blend_alpha: f64, // how far past its parents a blended gene can reach
+    pub mutation: MutationKind,
+    pub mutation_scale: f64, // multiplies the sigma of every gene
+    pub bounds: BoundsKind,
+    pub reinsertion: ReinsertionKind,
+    pub cma_sigma: f64, // initial CMA-ES step, as a share of each gene's range
+    pub seed: u64,
+    pub rules: Rules,
+    pub num_food: i32,
+    pub iterations: usize, // ticks per evaluation
+    pub threads: Option<usize>, // worker threads evaluating brains, all cores by default
+    pub episodes: usize, // episodes a brain is scored on, every generation
+    pub common_layouts: bool, // all brains of a generation play the same episodes
+    pub validation_episodes: usize, // the top brains of every generation compete on these for the hall of fame
+    pub holdout_episodes: usize, // never trained on nor picked on, only to measure how well the hall of fame generalises
+    pub hall_of_fame: usize, // best distinct brains kept in the model
+    pub seed_from: Option<String>, // model whose hall of fame starts the population
+    pub fitness: FitnessMode,
+    pub objectives: Vec<Term>, // weighted sum the brains maximise, the default of `fitness` when empty
+    pub group_size: usize, // worms sharing the food in a cooperative episode
+    pub survival_weight: f64, // cooperative fitness: share of the group alive at the end
+    pub extinction_weight: f64, // cooperative fitness: how long until the last worm starved
+    pub equality_weight: f64, // cooperative fitness: one minus the Gini coefficient of the food eaten
+    pub brain: BrainKind,
+    pub output: String,
+    pub population_output: Option<String>, // also save the final population here
+    pub checkpoint: Option<String>, // none for no checkpoint at all, not even when interrupted
+    pub checkpoint_every: usize, // generations between checkpoints, 0 for only when interrupted
+    pub metrics: Option<String>, // per-generation log, CSV or JSON lines
+    #[serde(skip)]
+    pub quiet: bool, // no report of every generation
+}
 
-fn mutate(brain: &mut WormBrain) {
-    let mut rng = rand::thread_rng();
-    if rng.gen_bool(0.2) {
-        brain.food_attraction += rng.gen_range(-0.1..0.1);
-        brain.food_attraction = brain.food_attraction.clamp(0.0, 1.0);
+impl Default for TrainConfig {
+    fn default() -> Self {
+        TrainConfig {
+            optimiser: Optimiser::Genetic,
+            population_size: 50,
+            generations: 50,
+            elite_fraction: 0.25,
+            mutation_rate: 0.2,
+            selection: SelectionKind::Best,
+            selection_ratio: 0.5,
+            tournament_size: 3,
+            crossover: CrossoverKind::Uniform,
+            blend_alpha: 0.5,
+            mutation: MutationKind::Uniform,
+            mutation_scale: 1.0,
+            bounds: BoundsKind::Clamp,
+            reinsertion: ReinsertionKind::Elitist,
+            cma_sigma: 0.3,
+            seed: 42,
+            rules: Rules::default(),
+            num_food: 10,
+            iterations: 500,
+            threads: None,
+            episodes: 5,
+            common_layouts: true,
+            validation_episodes: 20,
+            holdout_episodes: 20,
+            hall_of_fame: 10,
+            seed_from: None,
+            fitness: FitnessMode::Solo,
+            objectives: Vec::new(),
+            group_size: 10,
+            survival_weight: 1.0,
+            extinction_weight: 1.0,
+            equality_weight: 1.0,
+            brain: BrainKind::Weighted,
+            output: "worm_model.json".to_string(),
+            population_output: None,
+            checkpoint: Some("checkpoint.json".to_string()),
+            checkpoint_every: 10,
+            metrics: None,
+            quiet: false,
+        }
     }
-    if rng.gen_bool(0.2) {
-        brain.speed_factor += rng.gen_range(-0.2..0.2);
-        brain.speed_factor = brain.speed_factor.clamp(1.0, 3.0);
+}
+
+/**
+Runs the optimiser picked in `config`: the genetic algorithm with its operators, or CMA-ES.
+The best brains of every generation are scored on validation episodes,
+the ones that generalise best make the hall of fame of the returned model,
+returned along with the last generation. Their hold-out score is only reported.
+ */
+pub fn train<G: Genome>(config: &TrainConfig) -> (WormModel<G>, PopulationModel<G>) {
+    train_from(config, Vec::new())
+}
+
+/**
+Same as `train`, with `seeds` in the initial population and random brains for the rest.
+CMA-ES starts from the mean of that population.
+ */
+pub fn train_from<G: Genome>(config: &TrainConfig, seeds: Vec<G>) -> (WormModel<G>, PopulationModel<G>) {
+    let mut rng = seeded_rng(config.seed);
+    let mut population = seeds;
+    population.truncate(config.population_size);
+    while population.len() < config.population_size {
+        population.push(G::random(&mut rng));
     }
-    if rng.gen_bool(0.2) {
-        brain.life_threshold += rng.gen_range(-0.1..0.1);
-        brain.life_threshold = brain.life_threshold.clamp(0.0, 1.0);
+
+    resume(Checkpoint {
+        generation: 0,
+        population,
+        hall_of_fame: HallOfFame::new(config.hall_of_fame),
+        cma: None,
+        step_size: None,
+        config: config.clone(),
+        rng,
+    })
+}
+
+/**
+Goes on with the run saved in `checkpoint`, with its own settings.
+ */
+pub fn resume<G: Genome>(checkpoint: Checkpoint<G>) -> (WormModel<G>, PopulationModel<G>) {
+    let threads = checkpoint.config.threads;
+    with_threads(threads, || match checkpoint.config.optimiser {
+        Optimiser::Genetic => run_genetic(checkpoint),
+        Optimiser::CmaEs => run_cma(checkpoint),
+    })
+}
+
+fn run_genetic<G: Genome>(checkpoint: Checkpoint<G>) -> (WormModel<G>, PopulationModel<G>) {
+    let Checkpoint { generation: first_generation, population, hall_of_fame, step_size, config, mut rng, .. } = checkpoint;
+    let mut run = Run::new(config, first_generation, hall_of_fame);
+    let config = run.config.clone();
+    let fitness = RolloutFitness::new(&config);
+    let step_size = StepSize::new(step_size.unwrap_or_else(default_step_size));
+
+    let mut algorithm = genetic_algorithm()
+        .with_evaluation(fitness.clone())
+        .with_selection(Selector::new(&config))
+        .with_crossover(Breeder::new(&config, step_size.clone()))
+        .with_mutation(Mutator::with_step_size(&config, step_size.clone()))
+        .with_reinsertion(Reinserter::new(&config, fitness.clone(), step_size.clone()))
+        .with_initial_population(Population::with_individuals(population))
+        .build();
+
+    for generation_index in first_generation..config.generations {
+        let started = Instant::now();
+        let generation_rng = rng.clone();
+        let generation_step_size = step_size.get();
+        fitness.next_generation(&mut rng);
+
+        // genevo gets a fresh generator every generation so only ours has to be checkpointed
+        let mut algorithm_seed = [0u8; 32];
+        rng.fill(&mut algorithm_seed);
+        let step = algorithm.next(generation_index as u64 + 1, &mut Prng::from_seed(algorithm_seed))
+            .unwrap_or_else(|e| panic!("genetic algorithm failed: {:?}", e));
+
+        let generation = &step.evaluated_population;
+        let fitness_values: Vec<f64> = generation.fitness_values().iter().map(|value| value.0).collect();
+        run.record(generation_index, generation.individuals(), &fitness_values, &fitness, started);
+        if run.checkpoint(generation_index, generation_rng, None, Some(generation_step_size)) {
+            break;
+        }
     }
+
+    run.finish()
 }
 
+/**
+CMA-ES over the genes scaled to their range (or to where random brains start, for unbounded ones),
+so one `cma_sigma` suits every gene. A sample out of range is brought back by `config.bounds`
+before it is played, the distribution still moves towards the sample itself.
+ */
+fn run_cma<G: Genome>(checkpoint: Checkpoint<G>) -> (WormModel<G>, PopulationModel<G>) {
+    let Checkpoint { generation: first_generation, population, hall_of_fame, cma, config, mut rng, .. } = checkpoint;
+    let mut run = Run::new(config, first_generation, hall_of_fame);
+    let config = run.config.clone();
+    let fitness = RolloutFitness::new(&config);
 
-////////////////////////////////////////////////////////////////////////////////
+    let template = population[0].clone();
+    let specs = template.gene_specs();
+    let mut cma = cma.unwrap_or_else(|| {
+        let scaled: Vec<Vec<f64>> = population.iter()
+            .map(|brain| brain.gene_values().iter().zip(&specs).map(|(&gene, spec)| spec.scale(gene)).collect())
+            .collect();
+        let mean = (0..specs.len())
+            .map(|i| scaled.iter().map(|genes| genes[i]).sum::<f64>() / scaled.len() as f64)
+            .collect();
+        CmaEs::new(mean, config.cma_sigma)
+    });
 
+    for generation_index in first_generation..config.generations {
+        let started = Instant::now();
+        let generation_rng = rng.clone();
+        let generation_cma = cma.clone();
+        fitness.next_generation(&mut rng);
 
+        let samples = cma.ask(config.population_size, &mut rng);
+        let brains: Vec<G> = samples.iter()
+            .map(|sample| {
+                let genes: Vec<f64> = sample.iter()
+                    .zip(&specs)
+                    .map(|(&x, spec)| bound(spec.unscale(x), spec, config.bounds))
+                    .collect();
+                let mut brain = template.clone();
+                brain.set_gene_values(&genes);
+                brain
+            })
+            .collect();
+        let fitness_values: Vec<f64> = brains.par_iter()
+            .map(|brain| fitness.score(brain).mean)
+            .collect();
+        cma.tell(&samples, &fitness_values);
 
-fn verify_model2(model_json: &str) -> Result<(), Box<dyn std::error::Error>> {
-    // Parse the model from JSON string
-    let model: WormModel = serde_json::from_str(model_json)?;
-    println!("Loaded model with parameters:");
-    println!("  Food attraction: {:.2}", model.brain.food_attraction);
-    println!("  Speed factor: {:.2}", model.brain.speed_factor);
-    println!("  Life threshold: {:.2}", model.brain.life_threshold);
+        run.record(generation_index, &brains, &fitness_values, &fitness, started);
+        if run.checkpoint(generation_index, generation_rng, Some(generation_cma), None) {
+            break;
+        }
+    }
 
-    // Create test environment with randomly placed foods
-    let mut rng = rand::thread_rng();
-    let mut foods: Vec<Food> = (0..5)  // Start with 5 pieces of food
-        .map(|i| Food {
-            position: (
-                rng.gen_range(0..=100),
-                rng.gen_range(0..=100)
-            ),
-            value: rng.gen_range(2..=10)
-        })
-        .collect();
+    run.finish()
+}
 
-    // Initialize worm at center
-    let mut worm = Worm {
-        position: (50.0, 50.0),
-        life: 1.0,
-        food_consumed: 0,
-    };
+/**
+What every optimiser does with a generation once it is evaluated:
+feed the hall of fame, report, log and checkpoint.
+ */
+struct Run<G> {
+    config: TrainConfig,
+    first_generation: usize,
+    validation: Vec<Episode>,
+    holdout: Vec<Episode>,
+    hall_of_fame: HallOfFame<G>,
+    metrics_log: Option<MetricsLog>,
+    last_generation: Vec<G>,
+    last_generation_index: usize,
+}
+
+impl<G: Genome> Run<G> {
+    fn new(config: TrainConfig, first_generation: usize, hall_of_fame: HallOfFame<G>) -> Run<G> {
+        // from their own streams of the seed, so a resumed run plays the same ones
+        let episodes_from = |stream, n| {
+            let mut rng = seeded_rng(config.seed);
+            rng.set_stream(stream);
+            sample_episodes(n, &config.rules, config.num_food, &mut rng)
+        };
+        let validation = episodes_from(3, config.validation_episodes);
+        let holdout = episodes_from(1, config.holdout_episodes);
 
-    println!("\nInitial setup:");
-    println!("Worm starting position: ({:.1}, {:.1})", worm.position.0, worm.position.1);
-    println!("Food positions:");
-    for (i, food) in foods.iter().enumerate() {
-        println!("  Food {}: pos ({}, {}), value {}",
-                 i + 1,
-                 food.position.0,
-                 food.position.1,
-                 food.value);
+        let metrics_log = config.metrics.as_ref().and_then(|path| {
+            MetricsLog::open(path, first_generation > 0)
+                .map_err(|e| eprintln!("Failed to open the metrics log {}: {}", path, e))
+                .ok()
+        });
+
+        Run {
+            config,
+            first_generation,
+            validation,
+            holdout,
+            hall_of_fame,
+            metrics_log,
+            last_generation: Vec::new(),
+            last_generation_index: first_generation,
+        }
     }
 
-    // Run simulation
-    let max_steps = 1000;
+    fn record(&mut self, generation_index: usize, individuals: &[G], fitness_values: &[f64], fitness: &RolloutFitness, started: Instant) {
+        let config = &self.config;
+        let contenders = contenders(individuals, fitness_values, config.hall_of_fame.max(1));
+        let champion = contenders[0].0;
 
-    println!("\nStarting simulation...");
-    for step in 0..max_steps {
-        if worm.life <= 0.0 {
-            println!("\nWorm died at step {}", step);
-            break;
+        // brains already in the hall of fame keep their score, the same genes would score the same
+        let validations: Vec<Score> = contenders.par_iter()
+            .map(|(brain, _)| match self.hall_of_fame.find(brain) {
+                Some(entry) => entry.validation,
+                None => Score::of(&episode_scores(&share(*brain), config, &self.validation)),
+            })
+            .collect();
+
+        // only the newcomers it admits play the hold-out episodes,
+        // the best first so none of them is pushed out by a later one
+        let mut newcomers: Vec<usize> = (0..contenders.len())
+            .filter(|&i| self.hall_of_fame.find(contenders[i].0).is_none())
+            .collect();
+        newcomers.sort_by(|&a, &b| validations[b].mean.total_cmp(&validations[a].mean));
+        let mut admitted: Vec<usize> = Vec::new();
+        for i in newcomers {
+            if self.hall_of_fame.rank(validations[i].mean) + admitted.len() < self.hall_of_fame.size() {
+                admitted.push(i);
+            }
+        }
+        let holdouts: Vec<Score> = admitted.par_iter()
+            .map(|&i| Score::of(&episode_scores(&share(contenders[i].0), config, &self.holdout)))
+            .collect();
+        for (&i, holdout) in admitted.iter().zip(holdouts) {
+            let (brain, brain_fitness) = contenders[i];
+            self.hall_of_fame.consider(Champion {
+                brain: brain.clone(),
+                fitness: brain_fitness,
+                validation: validations[i],
+                holdout,
+                generation: generation_index + 1,
+            });
         }
 
-        if foods.is_empty() {
-            println!("\nAll food consumed at step {}", step);
-            break;
+        let episodes_played = fitness.take_episodes_played();
+        // not counted, it's only reported
+        let training = Score::of(&episode_scores(&share(champion), config, &fitness.episodes_for(champion)));
+        let mean_fitness = fitness_values.iter().sum::<f64>() / fitness_values.len() as f64;
+        let best = self.hall_of_fame.best().expect("a contender was considered");
+
+        if !config.quiet {
+            println!(
+                "Generation {}/{}: best {:.1} (variance {:.1}), mean {:.1}, validation {:.1} (variance {:.1}), champion so far: validation {:.1}, hold-out {:.1}",
+                generation_index + 1,
+                config.generations,
+                training.mean,
+                training.variance,
+                mean_fitness,
+                validations[0].mean,
+                validations[0].variance,
+                best.validation.mean,
+                best.holdout.mean
+            );
         }
 
-        // Calculate movement direction using brain
-        let direction = model.brain.calculate_direction(worm.position, &foods);
-
-        // Adjust speed based on life threshold
-        let speed = model.brain.speed_factor *
-            if worm.life < model.brain.life_threshold { 1.5 } else { 1.0 };
-
-        // Move worm
-        let new_x = worm.position.0 + direction.cos() * speed;
-        let new_y = worm.position.1 + direction.sin() * speed;
-
-        // Apply boundaries
-        worm.position.0 = new_x.max(0.0).min(100.0);
-        worm.position.1 = new_y.max(0.0).min(100.0);
-
-        // Check for food consumption
-        let initial_food_count = foods.len();
-        foods.retain(|food| {
-            let distance = ((worm.position.0 - food.position.0 as f64).powi(2)
-                + (worm.position.1 - food.position.1 as f64).powi(2)).sqrt();
-
-            if distance < 2.0 {
-                worm.food_consumed += food.value;
-                worm.life = (worm.life + 0.2).min(1.0);
-                false  // Remove the food
-            } else {
-                true   // Keep the food
+        // a resumed run replays a generation its log already has
+        let replayed = self.first_generation > 0 && generation_index == self.first_generation;
+        if let (Some(log), false) = (&mut self.metrics_log, replayed) {
+            let genes: Vec<Vec<f64>> = individuals.iter()
+                .map(|brain| brain.gene_values())
+                .collect();
+            let metrics = GenerationMetrics::new(
+                generation_index + 1,
+                fitness_values,
+                &genes,
+                best.holdout.mean,
+                episodes_played,
+                started.elapsed().as_secs_f64()
+            );
+            if let Err(e) = log.write(&metrics) {
+                eprintln!("Failed to write the metrics log: {}", e);
             }
-        });
+        }
+
+        self.last_generation = individuals.to_vec();
+        self.last_generation_index = generation_index;
+    }
 
-        // If food was consumed this step
-        if foods.len() < initial_food_count {
-            println!("\nStep {}: Food consumed!", step);
-            println!("  Position: ({:.1}, {:.1})", worm.position.0, worm.position.1);
-            println!("  Life: {:.2}", worm.life);
-            println!("  Total food consumed: {}", worm.food_consumed);
-            println!("  Remaining food: {}", foods.len());
+    /**
+    Saves a checkpoint when one is due or the run was interrupted, true if the run has to stop.
+    `rng`, `cma` and `step_size` are as they were when the generation started.
+     */
+    fn checkpoint(&self, generation_index: usize, rng: SimRng, cma: Option<CmaEs>, step_size: Option<f64>) -> bool {
+        let config = &self.config;
+        let stop = interrupted();
+        let periodic = config.checkpoint_every > 0 && (generation_index + 1) % config.checkpoint_every == 0;
+        if let (Some(path), true) = (&config.checkpoint, stop || periodic) {
+            let checkpoint = Checkpoint {
+                generation: generation_index,
+                population: self.last_generation.clone(),
+                hall_of_fame: self.hall_of_fame.clone(),
+                cma,
+                step_size,
+                config: config.clone(),
+                rng,
+            };
+            match save_model(&checkpoint, path) {
+                Ok(()) => println!("Checkpoint saved to {}", path),
+                Err(e) => eprintln!("Failed to save checkpoint to {}: {}", path, e),
+            }
         }
+        stop
+    }
+
+    fn finish(self) -> (WormModel<G>, PopulationModel<G>) {
+        let best = self.hall_of_fame.best().expect("at least one generation ran");
+        let metadata = Metadata {
+            generation: best.generation,
+            fitness: describe_fitness(&self.config),
+            config: self.config.clone(),
+        };
+        let population_metadata = Metadata {
+            generation: self.last_generation_index + 1,
+            ..metadata.clone()
+        };
+        let model = WormModel {
+            hall_of_fame: self.hall_of_fame.entries().to_vec(),
+            ..WormModel::new(best.brain.clone(), best.fitness, Some(metadata))
+        };
+
+        (model, PopulationModel::new(self.last_generation, Some(population_metadata)))
+    }
+}
 
-        // Decrease life based on movement
-        worm.life -= 0.001 * speed;
-
-        // Log status every 100 steps
-        if step % 100 == 0 {
-            println!("\nStep {} status:", step);
-            println!("  Position: ({:.1}, {:.1})", worm.position.0, worm.position.1);
-            println!("  Direction: {:.1}°", direction.to_degrees());
-            println!("  Speed: {:.2}", speed);
-            println!("  Life: {:.2}", worm.life);
-            println!("  Food consumed: {}", worm.food_consumed);
+/**
+The fittest brains of a generation with distinct genes and their fitness, at most `count`, the fittest first.
+ */
+fn contenders<'a, G: Genome>(individuals: &'a [G], fitness_values: &[f64], count: usize) -> Vec<(&'a G, f64)> {
+    let mut ranked: Vec<usize> = (0..individuals.len()).collect();
+    ranked.sort_by(|&a, &b| fitness_values[b].total_cmp(&fitness_values[a]));
+
+    let mut contenders: Vec<(&G, f64)> = Vec::new();
+    for i in ranked {
+        if contenders.len() == count {
+            break;
+        }
+        let genes = individuals[i].gene_values();
+        if !contenders.iter().any(|(brain, _)| brain.gene_values() == genes) {
+            contenders.push((&individuals[i], fitness_values[i]));
         }
     }
+    contenders
+}
+
+#[test]
+fn train_is_reproducible() {
+    let config = TrainConfig {
+        population_size: 10,
+        generations: 3,
+        iterations: 100,
+        ..TrainConfig::default()
+    };
+
+    let (first, population) = train::<WormBrain>(&config);
+    let (second, _) = train::<WormBrain>(&config);
+
+    assert_eq!(first.fitness, second.fitness);
+    assert_eq!(first.brain.speed_factor, second.brain.speed_factor);
+    assert_eq!(population.metadata.map(|metadata| metadata.generation), Some(config.generations));
+    assert_eq!(first.brain.life_threshold, second.brain.life_threshold);
+    assert_eq!(first.hall_of_fame[0].brain, first.brain);
+    assert!(first.hall_of_fame.len() <= config.hall_of_fame);
+}
+
+#[test]
+fn independent_layouts_are_reproducible() {
+    let config = TrainConfig {
+        population_size: 10,
+        generations: 2,
+        iterations: 100,
+        episodes: 3,
+        common_layouts: false,
+        validation_episodes: 5,
+        holdout_episodes: 5,
+        ..TrainConfig::default()
+    };
+
+    let (first, _) = train::<WormBrain>(&config);
+    let (second, _) = train::<WormBrain>(&config);
+
+    assert_eq!(first.fitness, second.fitness);
+    assert_eq!(first.brain, second.brain);
+}
+
+#[test]
+fn same_result_on_any_number_of_threads() {
+    let config = TrainConfig {
+        population_size: 10,
+        generations: 2,
+        iterations: 100,
+        episodes: 4,
+        ..TrainConfig::default()
+    };
+
+    let (single, single_population) = train::<WormBrain>(&TrainConfig { threads: Some(1), ..config.clone() });
+    let (many, many_population) = train::<WormBrain>(&TrainConfig { threads: Some(4), ..config });
+
+    assert_eq!(single.fitness, many.fitness);
+    assert_eq!(single.brain, many.brain);
+    assert_eq!(single_population.brains, many_population.brains);
+}
+
+#[test]
+fn resumed_run_matches_uninterrupted_run() {
+    let checkpoint = std::env::temp_dir().join("resumed_run_matches_uninterrupted_run.json");
+
+    for optimiser in [Optimiser::Genetic, Optimiser::CmaEs] {
+        let config = TrainConfig {
+            optimiser,
+            mutation: MutationKind::SelfAdaptive, // its step size is checkpointed too
+            population_size: 10,
+            generations: 6,
+            iterations: 100,
+            episodes: 2,
+            checkpoint: Some(checkpoint.to_string_lossy().into_owned()),
+            checkpoint_every: 3,
+            ..TrainConfig::default()
+        };
+
+        let (whole, whole_population) = train::<WormBrain>(&config);
 
-    // Print final statistics
-    println!("\nSimulation complete!");
-    println!("Final statistics:");
-    println!("  Total food consumed: {}", worm.food_consumed);
-    println!("  Final position: ({:.1}, {:.1})", worm.position.0, worm.position.1);
-    println!("  Final life: {:.2}", worm.life);
-    println!("  Remaining food: {}", foods.len());
+        // the checkpoint of generation 3, as if the run had stopped there
+        train::<WormBrain>(&TrainConfig { generations: 3, ..config.clone() });
+        let mut saved = crate::checkpoint::load_checkpoint::<WormBrain>(&checkpoint.to_string_lossy()).unwrap();
+        saved.config.generations = 6;
+        let (resumed, resumed_population) = resume(saved);
 
-    Ok(())
+        assert_eq!(whole.fitness, resumed.fitness, "{:?}", optimiser);
+        assert_eq!(whole.brain, resumed.brain, "{:?}", optimiser);
+        assert_eq!(whole_population.brains, resumed_population.brains, "{:?}", optimiser);
+    }
+    let _ = std::fs::remove_file(&checkpoint);
 }
 
+/**
+`cargo test --release parallel_speedup -- --ignored --nocapture`
+times a large multi-episode training run on one thread and on every core.
+ */
 #[test]
-fn verify() {
-    let model_json = r#"{"brain":{"food_attraction":0.7620645569679378,"speed_factor":1.8947409180791541,"life_threshold":0.31420650864268596},"fitness":8}"#;
-    if let Err(e) = verify_model2(model_json) {
-        eprintln!("Error verifying model: {}", e);
+#[ignore]
+fn parallel_speedup() {
+    let config = TrainConfig {
+        population_size: 200,
+        generations: 3,
+        episodes: 10,
+        validation_episodes: 10,
+        holdout_episodes: 10,
+        ..TrainConfig::default()
+    };
+
+    let time = |threads: Option<usize>| {
+        let start = std::time::Instant::now();
+        train::<WormBrain>(&TrainConfig { threads, ..config.clone() });
+        start.elapsed()
+    };
+
+    let single = time(Some(1));
+    let parallel = time(None);
+    println!(
+        "1 thread: {:.2?}, {} threads: {:.2?}, speed-up x{:.1}",
+        single,
+        rayon::current_num_threads(),
+        parallel,
+        single.as_secs_f64() / parallel.as_secs_f64()
+    );
+}
+
+/**
+Score of every brain over the same seeded episodes,
+so steering strategies can be compared on equal terms.
+ */
+pub fn benchmark(brains: &[(String, SharedBrain)], config: &TrainConfig, episodes: usize) -> Vec<(String, Score)> {
+    let mut rng = seeded_rng(config.seed);
+    let episodes = sample_episodes(episodes, &config.rules, config.num_food, &mut rng);
+
+    with_threads(config.threads, || {
+        brains.par_iter()
+            .map(|(name, brain)| {
+                let scores = episode_scores(brain, config, &episodes);
+                (name.clone(), Score::of(&scores))
+            })
+            .collect()
+    })
+}
+
+/**
+Runs `work` on a pool of `threads` workers, or on rayon's global pool (one per core).
+ */
+pub(crate) fn with_threads<T: Send>(threads: Option<usize>, work: impl FnOnce() -> T + Send) -> T {
+    match threads {
+        Some(threads) => rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("failed to start the worker threads")
+            .install(work),
+        None => work(),
     }
+}
+
+pub(crate) fn evaluate_brain(
+    brain: &SharedBrain,
+    rules: &Rules,
+    foods: &[Food],
+    seed: u64,
+    iterations: usize,
+    hunger_threshold: f64
+) -> Rollout {
+    play(spawn_single_worm(rules, brain.clone(), foods, seeded_rng(seed)), iterations, hunger_threshold)
+}
+
+/**
+A world with one worm in the middle of the arena and the given food layout.
+ */
+fn spawn_single_worm(rules: &Rules, brain: SharedBrain, foods: &[Food], rng: SimRng) -> World {
+    let mut worm = Worm::new(
+        0,
+        Vec2::new(rules.width / 2.0, rules.height / 2.0),
+        Vec2::new(1.0, 0.0),
+        0.0,
+    );
+    worm.brain = Some(brain);
+
+    World::new(rules.clone(), vec![worm], foods.to_vec(), rng)
+}
+
+pub(crate) fn random_brain<R: Rng>(rng: &mut R) -> WormBrain {
+    WormBrain::from_genes(&GENES.map(|gene| rng.gen_range(gene.initial.0..gene.initial.1)))
+}
+
+/**
+Each gene from either parent.
+ */
+fn crossover<R: Rng>(parent1: &WormBrain, parent2: &WormBrain, rng: &mut R) -> WormBrain {
+    let genes: Vec<f64> = parent1.genes().into_iter()
+        .zip(parent2.genes())
+        .map(|(gene1, gene2)| if rng.gen_bool(0.5) { gene1 } else { gene2 })
+        .collect();
+    WormBrain::from_genes(&genes)
+}
+
+/**
+Each gene blended from both parents, brought back in range like a mutated one.
+ */
+fn blend<R: Rng>(parent1: &WormBrain, parent2: &WormBrain, alpha: f64, bounds: BoundsKind, rng: &mut R) -> WormBrain {
+    let genes: Vec<f64> = parent1.genes().into_iter()
+        .zip(parent2.genes())
+        .zip(&GENES)
+        .map(|((gene1, gene2), spec)| bound(blend_gene(gene1, gene2, alpha, rng), spec, bounds))
+        .collect();
+    WormBrain::from_genes(&genes)
 }
\ No newline at end of file
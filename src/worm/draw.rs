@@ -4,6 +4,8 @@ use raylib::math::{Rectangle, Vector2};
 use crate::math::vector_between;
 use crate::worm::Worm;
 
+const WORM_COLOR: Color = Color::new(85, 239, 196, 255);
+
 
 pub fn draw_worms(
     d2d: &mut RaylibMode2D<RaylibDrawHandle>,
@@ -37,8 +39,8 @@ fn draw_worm(
         1f32.min(percentage_animation * 2.0) // 2x faster to reach 100%
     );
 
-    let color_head = worm.color;
-    let color_tail = worm.color.brightness(-0.2);
+    let color_head = WORM_COLOR;
+    let color_tail = WORM_COLOR.brightness(-0.2);
 
     let body_center = vector_between(
         circle_tail,
@@ -4,17 +4,40 @@ pub mod r#move;
 pub mod generate;
 pub mod search;
 
-use raylib::color::Color;
-use raylib::drawing::{RaylibDraw};
-use raylib::math::{Vector2};
+use crate::math::Vec2;
+use crate::brain::SharedBrain;
 
+#[derive(Debug, Clone)]
 pub struct Worm {
-    pub pos: Vector2, // in pixels
-    pub prev_pos: Vector2, // in pixels
-    pub dir: Vector2,
-    pub color: Color, // constant
+    pub id: usize, // stable across deaths, unlike the index
+    pub pos: Vec2, // in pixels
+    pub prev_pos: Vec2, // in pixels
+    pub dir: Vec2,
     pub rotation: f32, // in radians
     pub speed: f32, // from 0 to 1
     pub ray: f32, // constant
     pub life: f32, // from 0 to 1
+    pub food_eaten: f32,
+    pub distance: f32, // travelled since it spawned, in pixels
+    pub eating: bool, // its brain can hold it back from the food within reach
+    pub brain: Option<SharedBrain>, // steers this worm, if any
+}
+
+impl Worm {
+    pub fn new(id: usize, pos: Vec2, dir: Vec2, speed: f32) -> Worm {
+        Worm {
+            id,
+            pos,
+            prev_pos: pos,
+            dir,
+            rotation: 0.0,
+            speed,
+            ray: 10.0,
+            life: 1.0,
+            food_eaten: 0.0,
+            distance: 0.0,
+            eating: true,
+            brain: None,
+        }
+    }
 }
\ No newline at end of file
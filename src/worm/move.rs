@@ -1,14 +1,18 @@
 use crate::math::add_vec2;
+use crate::sim::rules::Rules;
 use crate::worm::Worm;
 
-pub fn move_worms(worms: &mut Vec<Worm>) {
+pub fn move_worms(worms: &mut Vec<Worm>, rules: &Rules, dt: f32) {
     for worm in worms.iter_mut() {
-        move_worm(worm);
+        move_worm(worm, rules, dt);
     }
 }
 
-fn move_worm(worm: &mut Worm) {
+fn move_worm(worm: &mut Worm, rules: &Rules, dt: f32) {
     worm.prev_pos = worm.pos;
-    worm.pos = add_vec2(worm.pos, worm.dir, worm.speed * 10.0);
+    worm.pos = add_vec2(worm.pos, worm.dir, worm.speed * rules.step_scale * dt);
+    worm.pos.x = worm.pos.x.clamp(0.0, rules.width);
+    worm.pos.y = worm.pos.y.clamp(0.0, rules.height);
+    worm.distance += worm.pos.distance_to(worm.prev_pos);
     worm.rotation = worm.dir.y.atan2(worm.dir.x);
 }
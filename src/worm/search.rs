@@ -1,6 +1,7 @@
 use raylib::camera::Camera2D;
 use raylib::color::Color;
 use raylib::drawing::{RaylibDraw, RaylibDrawHandle, RaylibMode2D};
+use crate::render::from_vector2;
 use crate::worm::Worm;
 
 pub fn highlight_selected_worm<'a>(
@@ -9,10 +10,10 @@ pub fn highlight_selected_worm<'a>(
     camera: &Camera2D
 ) -> Option<&'a Worm> {
 
-    let cur_mouse_pos = d2d.get_screen_to_world2D(
+    let cur_mouse_pos = from_vector2(d2d.get_screen_to_world2D(
         d2d.get_mouse_position(),
         camera
-    );
+    ));
 
     let closest_worm = worms
         .iter()
@@ -1,36 +1,46 @@
-use raylib::check_collision_circles;
 use crate::food::{Food, ray_of_food};
+use crate::sim::rules::Rules;
 use crate::worm::Worm;
 
 /**
 Based on how fast the worms are moving, they will lose energy.
+Dead worms are moved from `worms` to `dead`.
  */
-pub fn starve_worms(worms: &mut Vec<Worm>) {
-
-    let factor = 0.001;
+pub fn starve_worms(worms: &mut Vec<Worm>, dead: &mut Vec<Worm>, rules: &Rules, dt: f32) {
 
     for worm in worms.iter_mut() {
         // TODO: this should not be a linear punishment, it should be exponential (sprinting consumes much more energy)
-        worm.life -= factor * worm.speed;
+        worm.life -= rules.drain * worm.speed * dt;
     }
 
-    worms.retain(|worm| worm.life > 0.0);
-}
+    let (alive, starved): (Vec<Worm>, Vec<Worm>) = worms
+        .drain(..)
+        .partition(|worm| worm.life > 0.0);
 
-pub fn feed_worms(worms: &mut Vec<Worm>, food: &mut Vec<Food>) {
-    let max_eat:f32 = 1.0;
+    *worms = alive;
+    dead.extend(starved);
+}
 
+pub fn feed_worms(worms: &mut Vec<Worm>, food: &mut Vec<Food>, rules: &Rules, dt: f32) {
     // For each worm, check if it is colliding with any food
     for worm in worms.iter_mut() {
+        if !worm.eating {
+            continue;
+        }
+
         for f in food.iter_mut() {
             if f.amount <= 0.0 {
                 continue;
             }
 
-            if check_collision_circles(worm.pos, worm.ray, f.pos, ray_of_food(f)) {
-                worm.life = (worm.life + 2.0 * max_eat).min(1.0);
-                f.amount -= f.amount.min(max_eat) / 100f32;
+            if worm.pos.distance_to(f.pos) <= rules.eat_radius + ray_of_food(f, rules) {
+                let bite = f.amount.min(rules.bite * dt);
+                f.amount -= bite;
+                worm.food_eaten += bite;
+                worm.life = (worm.life + rules.energy_per_food * bite).min(1.0);
             }
         }
     }
+
+    food.retain(|f| f.amount > 0.0);
 }
\ No newline at end of file